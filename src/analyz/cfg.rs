@@ -0,0 +1,101 @@
+//! Control-flow analyses of MIR bodies: predecessors, dominators, and natural loops.  These are
+//! exported alongside each function when `MIR_JSON_CFG` is set, so consumers don't need to
+//! recompute them.
+use rustc::mir::{self, BasicBlock, BodyAndCache};
+use rustc_index::vec::IndexVec;
+use serde_json;
+use std::collections::BTreeSet;
+
+fn block_name(bb: BasicBlock) -> String {
+    format!("{:?}", bb)
+}
+
+/// Describe the control-flow graph of `body`: the predecessors and immediate dominator of each
+/// block, back edges (edges whose target dominates their source), and the natural loops formed by
+/// those back edges.  Loops sharing a header are merged.  Unreachable blocks have no immediate
+/// dominator and belong to no loop.
+///
+/// If `skip_cleanup` is set, cleanup blocks and the unwind edges leading to them are omitted,
+/// matching the blocks exported in panic-abort mode.  Cleanup blocks never branch back to normal
+/// blocks, so this doesn't change the dominators of the remaining blocks.
+pub fn cfg_json(body: &BodyAndCache, skip_cleanup: bool) -> serde_json::Value {
+    let body = body.unwrap_read_only();
+    let blocks = body.basic_blocks();
+    let dominators = body.dominators();
+    let included = |bb: BasicBlock| {
+        dominators.is_reachable(bb) && !(skip_cleanup && blocks[bb].is_cleanup)
+    };
+
+    let mut preds: IndexVec<BasicBlock, BTreeSet<BasicBlock>> =
+        IndexVec::from_elem_n(BTreeSet::new(), blocks.len());
+    for (bb, bb_preds) in body.predecessors().iter_enumerated() {
+        if included(bb) {
+            preds[bb].extend(bb_preds.iter().cloned().filter(|&p| included(p)));
+        }
+    }
+
+    let mut preds_json = serde_json::Map::new();
+    let mut idom_json = serde_json::Map::new();
+    for (bb, _) in mir::traversal::reverse_postorder(&body) {
+        if !included(bb) {
+            continue;
+        }
+        preds_json.insert(
+            block_name(bb),
+            preds[bb].iter().map(|&p| block_name(p)).collect::<Vec<_>>().into(),
+        );
+        let d = if bb == mir::START_BLOCK {
+            None
+        } else {
+            Some(dominators.immediate_dominator(bb))
+        };
+        idom_json.insert(block_name(bb), json!(d.map(block_name)));
+    }
+
+    let mut back_edges = Vec::new();
+    let mut loop_headers = Vec::new();
+    let mut loop_bodies: IndexVec<BasicBlock, BTreeSet<BasicBlock>> =
+        IndexVec::from_elem_n(BTreeSet::new(), blocks.len());
+    for (bb, data) in mir::traversal::reverse_postorder(&body) {
+        if !included(bb) {
+            continue;
+        }
+        let succs = data.terminator().successors()
+            .cloned()
+            .filter(|&succ| included(succ))
+            .collect::<BTreeSet<_>>();
+        for succ in succs {
+            if !dominators.is_dominated_by(bb, succ) {
+                continue;
+            }
+            back_edges.push(json!({ "from": block_name(bb), "to": block_name(succ) }));
+
+            // The natural loop of this edge is the header plus every block that can reach `bb`
+            // without passing through the header.
+            let header = succ;
+            if loop_bodies[header].is_empty() {
+                loop_headers.push(header);
+                loop_bodies[header].insert(header);
+            }
+            let mut worklist = vec![bb];
+            while let Some(x) = worklist.pop() {
+                if loop_bodies[header].insert(x) {
+                    worklist.extend(preds[x].iter().cloned());
+                }
+            }
+        }
+    }
+
+    loop_headers.sort();
+    let loops = loop_headers.iter().map(|&h| json!({
+        "header": block_name(h),
+        "blocks": loop_bodies[h].iter().map(|&bb| block_name(bb)).collect::<Vec<_>>(),
+    })).collect::<Vec<_>>();
+
+    json!({
+        "predecessors": preds_json,
+        "idom": idom_json,
+        "back_edges": back_edges,
+        "loops": loops,
+    })
+}
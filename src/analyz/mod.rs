@@ -1,23 +1,31 @@
 #![macro_use]
 
-use rustc::ty::{self, TyCtxt, List};
+use rustc::ty::{self, TyCtxt, List, TypeFoldable};
 use rustc::mir::{self, Body};
 use rustc_ast::{ast, token, tokenstream, visit};
-use rustc_hir::def::DefKind;
+use rustc_hir as hir;
+use rustc_hir::def::{DefKind, Res};
 use rustc_hir::def_id::{self, DefId, LOCAL_CRATE};
+use rustc::middle::codegen_fn_attrs::CodegenFnAttrFlags;
 use rustc::mir::mono::MonoItem;
+use rustc_data_structures::stable_hasher::StableHasher;
 use rustc_session::config::OutputType;
 use rustc_session;
 use rustc_index::vec::Idx;
 use rustc_interface::Queries;
 use rustc_mir::monomorphize::collector::{self, MonoItemCollectionMode};
+use rustc_target::abi::Size;
 use rustc_target::spec::abi;
 use rustc_session::Session;
 use rustc_span::symbol::Symbol;
 use rustc_span::Span;
+use rustc_span::hygiene::ExpnKind;
+use rustc_span::{BytePos, SourceFile};
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
 use std::fmt::Write as FmtWrite;
+use std::env;
 use std::io;
 use std::iter;
 use std::fs::File;
@@ -30,9 +38,10 @@ use serde_cbor;
 #[macro_use]
 mod to_json;
 mod ty_json;
+mod cfg;
 use analyz::to_json::*;
 use analyz::ty_json::*;
-use lib_util::{self, JsonOutput, EntryKind};
+use lib_util::{self, JsonOutput, EntryKind, env_flag};
 
 basic_json_enum_impl!(mir::BinOp);
 
@@ -63,16 +72,24 @@ impl<'tcx> ToJson<'tcx> for mir::AggregateKind<'tcx> {
             &mir::AggregateKind::Adt(_, _, _, _, _) => {
                 panic!("adt should be handled upstream")
             }
-            &mir::AggregateKind::Closure(_, _) => {
+            &mir::AggregateKind::Closure(def_id, substs) => {
+                // mir-verifier uses the same representation for closures as it does for tuples.
+                // The closure type carries the closure kind, signature, and captures, which are
+                // useful for describing the value.
+                let ty = mir.state.tcx.mk_closure(def_id, substs);
                 json!({
                     "kind": "Closure",
-                    // mir-verifier uses the same representation for closures as it does for
-                    // tuples, so no additional information is needed.
+                    "ty": ty.to_json(mir),
                 })
             }
-            &mir::AggregateKind::Generator(_, _, _,) => {
-                // TODO
-                json!({"kind": "Generator"})
+            &mir::AggregateKind::Generator(def_id, substs, movability) => {
+                // The generator type carries the upvar, resume, yield, and return types, along
+                // with the layout of the saved locals.
+                let ty = mir.state.tcx.mk_generator(def_id, substs, movability);
+                json!({
+                    "kind": "Generator",
+                    "ty": ty.to_json(mir),
+                })
             }
         }
     }
@@ -174,7 +191,10 @@ impl<'tcx> ToJson<'tcx> for mir::Rvalue<'tcx> {
                         "kind": "UnsizeVtable",
                         "vtable": vtable_name(mir, vtable_desc),
                     });
-                    mir.used.vtables.insert(vtable_desc);
+                    // Vtables for generic types can't be built until the type is known.
+                    if !vtable_desc.needs_subst() {
+                        mir.used.vtables.insert(vtable_desc);
+                    }
                 }
                 j
             }
@@ -289,7 +309,48 @@ impl ToJson<'_> for mir::Field {
     }
 }
 
-basic_json_impl!(mir::AssertMessage<'a>, 'a);
+impl<'tcx> ToJson<'tcx> for mir::AssertMessage<'tcx> {
+    fn to_json(&self, mir: &mut MirState<'_, 'tcx>) -> serde_json::Value {
+        match self {
+            &mir::AssertKind::BoundsCheck { ref len, ref index } => {
+                json!({
+                    "kind": "BoundsCheck",
+                    "len": len.to_json(mir),
+                    "index": index.to_json(mir),
+                })
+            }
+            &mir::AssertKind::Overflow(ref op, ref l, ref r) => {
+                json!({
+                    "kind": "Overflow",
+                    "op": op.to_json(mir),
+                    "L": l.to_json(mir),
+                    "R": r.to_json(mir),
+                })
+            }
+            &mir::AssertKind::OverflowNeg => {
+                json!({"kind": "OverflowNeg"})
+            }
+            &mir::AssertKind::DivisionByZero => {
+                json!({"kind": "DivisionByZero"})
+            }
+            &mir::AssertKind::RemainderByZero => {
+                json!({"kind": "RemainderByZero"})
+            }
+            &mir::AssertKind::ResumedAfterReturn(ref gen_kind) => {
+                json!({
+                    "kind": "ResumedAfterReturn",
+                    "generator_kind": format!("{:?}", gen_kind),
+                })
+            }
+            &mir::AssertKind::ResumedAfterPanic(ref gen_kind) => {
+                json!({
+                    "kind": "ResumedAfterPanic",
+                    "generator_kind": format!("{:?}", gen_kind),
+                })
+            }
+        }
+    }
+}
 
 impl<'tcx> ToJson<'tcx> for mir::Operand<'tcx> {
     fn to_json(&self, mir: &mut MirState<'_, 'tcx>) -> serde_json::Value {
@@ -320,8 +381,15 @@ impl<'tcx> ToJson<'tcx> for mir::LocalDecl<'tcx> {
             "ty": self.ty.to_json(mir),
             // We specifically record whether the variable's type is zero-sized, because rustc
             // allows reading and taking refs of uninitialized zero-sized locals.
-            "is_zst": mir.state.tcx.layout_of(ty::ParamEnv::reveal_all().and(self.ty))
-                .expect("failed to get layout").is_zst(),
+            // The layout of a generic type is unknown, in which case this is `null`.  We don't
+            // call `layout_of` at all for these, since normalizing a projection under
+            // `reveal_all` can ICE.
+            "is_zst": if self.ty.needs_subst() {
+                None
+            } else {
+                Some(mir.state.tcx.layout_of(ty::ParamEnv::reveal_all().and(self.ty))
+                    .expect("failed to get layout").is_zst())
+            },
         })
     }
 }
@@ -356,9 +424,35 @@ impl<'tcx> ToJson<'tcx> for mir::Statement<'tcx> {
             &mir::StatementKind::StorageDead(l) => {
                 json!({"kind": "StorageDead", "sdvar": local_json(mir, l)})
             }
-            &mir::StatementKind::InlineAsm { .. } => {
-                // TODO
-                json!({"kind": "InlineAsm"})
+            &mir::StatementKind::InlineAsm(ref asm) => {
+                let inner = &asm.asm;
+                json!({
+                    "kind": "InlineAsm",
+                    "asm": inner.asm.to_json(mir),
+                    "outputs": inner.outputs.iter().zip(asm.outputs.iter()).map(|(out, place)| {
+                        json!({
+                            "constraint": out.constraint.to_json(mir),
+                            "is_rw": out.is_rw,
+                            "is_indirect": out.is_indirect,
+                            "place": place.to_json(mir),
+                        })
+                    }).collect::<Vec<_>>(),
+                    "inputs": inner.inputs.iter().zip(asm.inputs.iter()).map(|(c, inp)| {
+                        let &(span, ref op) = inp;
+                        let mut j = json!({
+                            "constraint": c.to_json(mir),
+                            "op": op.to_json(mir),
+                        });
+                        set_span_json(mir, &mut j, "pos", span);
+                        j
+                    }).collect::<Vec<_>>(),
+                    "clobbers": inner.clobbers.to_json(mir),
+                    "options": {
+                        "volatile": inner.volatile,
+                        "alignstack": inner.alignstack,
+                        "dialect": format!("{:?}", inner.dialect),
+                    },
+                })
             }
             &mir::StatementKind::Retag { .. } => {
                 // TODO
@@ -372,7 +466,8 @@ impl<'tcx> ToJson<'tcx> for mir::Statement<'tcx> {
                 json!({"kind": "Nop"})
             }
         };
-        j["pos"] = self.source_info.span.to_json(mir);
+        set_span_json(mir, &mut j, "pos", self.source_info.span);
+        j["scope"] = self.source_info.scope.as_usize().into();
         j
     }
 }
@@ -384,11 +479,17 @@ fn operand_span(mir: &MirState, op: &mir::Operand) -> Option<Span> {
 
 impl ToJson<'_> for Span {
     fn to_json(&self, mir: &mut MirState) -> serde_json::Value {
-        let source_map = mir.state.session.source_map();
+        if mir.state.opts.structured_spans {
+            return structured_span_json(mir, *self);
+        }
+        let state = mir.state;
+        let source_map = state.session.source_map();
         let callsite = self.source_callsite();
+        mir.used.sources.insert(source_map.lookup_source_file(self.lo()).start_pos);
         let s = if callsite == *self {
             source_map.span_to_string(*self)
         } else {
+            mir.used.sources.insert(source_map.lookup_source_file(callsite.lo()).start_pos);
             format!(
                 "{} !{}",
                 source_map.span_to_string(*self),
@@ -399,6 +500,93 @@ impl ToJson<'_> for Span {
     }
 }
 
+/// Set `j[key]` to the serialized form of `span`.  In the default (string) mode, this also sets
+/// `j["{key}_file"]` to the ID of the `sources` entry for the span's file, and
+/// `j["{key}_callsite_file"]` to that of its macro callsite, if it has one in a different file.
+/// Structured spans carry their own `file_id`s instead.
+fn set_span_json(mir: &mut MirState, j: &mut serde_json::Value, key: &str, span: Span) {
+    j[key] = span.to_json(mir);
+    if mir.state.opts.structured_spans {
+        return;
+    }
+    let source_map = mir.state.session.source_map();
+    let file = source_map.lookup_source_file(span.lo());
+    j[format!("{}_file", key)] = source_file_id_str(&file).into();
+    let callsite = span.source_callsite();
+    if callsite != span {
+        let callsite_file = source_map.lookup_source_file(callsite.lo());
+        if callsite_file.start_pos != file.start_pos {
+            j[format!("{}_callsite_file", key)] = source_file_id_str(&callsite_file).into();
+        }
+    }
+}
+
+/// Build the structured form of `span`.  Each entry in `expansion` describes one macro expansion
+/// or desugaring, innermost first, along with the location where it was invoked.
+fn structured_span_json(mir: &mut MirState, span: Span) -> serde_json::Value {
+    let mut j = span_pos_json(mir, span);
+    let mut expansion = Vec::new();
+    let mut cur = span;
+    while cur.from_expansion() {
+        let data = cur.ctxt().outer_expn_data();
+        let mut frame = match data.kind {
+            ExpnKind::Root => break,
+            ExpnKind::Macro(kind, name) => json!({
+                "kind": "Macro",
+                "macro_kind": format!("{:?}", kind),
+                "name": name.to_string(),
+            }),
+            ExpnKind::AstPass(pass) => json!({
+                "kind": "AstPass",
+                "name": format!("{:?}", pass),
+            }),
+            ExpnKind::Desugaring(kind) => json!({
+                "kind": "Desugaring",
+                "name": format!("{:?}", kind),
+            }),
+        };
+        frame["call_site"] = span_pos_json(mir, data.call_site);
+        expansion.push(frame);
+        cur = data.call_site;
+    }
+    j["expansion"] = expansion.into();
+    j
+}
+
+/// Get the ID of a source file's entry in the `sources` table.  This depends on both the path and
+/// the contents, so different versions of a file get distinct entries.
+fn source_file_id_str(file: &SourceFile) -> String {
+    let mut h = StableHasher::new();
+    file.name_hash.hash(&mut h);
+    file.src_hash.hash(&mut h);
+    let hash: u64 = h.finish();
+    format!("src::{:016x}", hash)
+}
+
+/// Describe the file and start/end positions of `span`.  Lines and columns are 1-based, matching
+/// the string form of spans.  Byte offsets are relative to the start of the file.
+fn span_pos_json(mir: &mut MirState, span: Span) -> serde_json::Value {
+    let state = mir.state;
+    let source_map = state.session.source_map();
+    let lo = source_map.lookup_char_pos(span.lo());
+    let hi = source_map.lookup_char_pos(span.hi());
+    mir.used.sources.insert(lo.file.start_pos);
+    json!({
+        "file": lo.file.name.to_string(),
+        "file_id": source_file_id_str(&lo.file),
+        "start": {
+            "line": lo.line,
+            "col": lo.col.0 + 1,
+            "byte": (span.lo() - lo.file.start_pos).0,
+        },
+        "end": {
+            "line": hi.line,
+            "col": hi.col.0 + 1,
+            "byte": (span.hi() - hi.file.start_pos).0,
+        },
+    })
+}
+
 impl<'tcx> ToJson<'tcx> for mir::Terminator<'tcx> {
     fn to_json(&self, mir: &mut MirState<'_, 'tcx>) -> serde_json::Value {
         let mut j = match &self.kind {
@@ -416,14 +604,15 @@ impl<'tcx> ToJson<'tcx> for mir::Terminator<'tcx> {
                 let discr_span = mir.match_span_map.get(&self.source_info.span).cloned()
                     .or_else(|| operand_span(mir, discr))
                     .unwrap_or(self.source_info.span);
-                json!({
+                let mut j = json!({
                     "kind": "SwitchInt",
                     "discr": discr.to_json(mir),
-                    "discr_span": discr_span.to_json(mir),
                     "switch_ty": switch_ty.to_json(mir),
                     "values": vals,
                     "targets": targets.to_json(mir)
-                })
+                });
+                set_span_json(mir, &mut j, "discr_span", discr_span);
+                j
             }
             &mir::TerminatorKind::Resume => {
                 json!({"kind": "Resume"})
@@ -499,10 +688,18 @@ impl<'tcx> ToJson<'tcx> for mir::Terminator<'tcx> {
             &mir::TerminatorKind::Abort => {
                 json!({ "kind": "Abort" })
             }
-            &mir::TerminatorKind::Yield { .. } => {
-                // TODO
+            &mir::TerminatorKind::Yield {
+                ref value,
+                ref resume,
+                ref resume_arg,
+                ref drop,
+            } => {
                 json!({
-                    "kind": "Yield"
+                    "kind": "Yield",
+                    "value": value.to_json(mir),
+                    "resume": resume.to_json(mir),
+                    "resume_arg": resume_arg.to_json(mir),
+                    "drop": drop.to_json(mir),
                 })
             }
             &mir::TerminatorKind::FalseEdges { .. } => {
@@ -521,7 +718,16 @@ impl<'tcx> ToJson<'tcx> for mir::Terminator<'tcx> {
                 json!({ "kind": "GeneratorDrop" })
             }
         };
-        j["pos"] = self.source_info.span.to_json(mir);
+        if mir.state.opts.panic_abort {
+            // Cleanup blocks are omitted in this mode, so drop all edges leading to them.
+            for &key in &["unwind", "cleanup"] {
+                if let Some(x) = j.get_mut(key) {
+                    *x = serde_json::Value::Null;
+                }
+            }
+        }
+        set_span_json(mir, &mut j, "pos", self.source_info.span);
+        j["scope"] = self.source_info.scope.as_usize().into();
         j
     }
 }
@@ -534,7 +740,8 @@ impl<'tcx> ToJson<'tcx> for mir::BasicBlockData<'tcx> {
         }
         json!({
             "data": sts,
-            "terminator": self.terminator().to_json(mir)
+            "terminator": self.terminator().to_json(mir),
+            "is_cleanup": self.is_cleanup,
         })
     }
 }
@@ -561,6 +768,9 @@ fn mir_body(
 
     let mut blocks = Vec::new();
     for bb in mir.basic_blocks().indices() {
+        if ms.state.opts.panic_abort && mir[bb].is_cleanup {
+            continue;
+        }
         blocks.push(
             json!({
                 "blockid": bb.to_json(ms),
@@ -568,9 +778,47 @@ fn mir_body(
             }),
         );
     }
+    // Map user variables to the places that hold them.  In this version of rustc, debug info
+    // always refers to a place; constants propagated into a variable are not recorded.
+    let mut debug_info = Vec::with_capacity(mir.var_debug_info.len());
+    for vdi in &mir.var_debug_info {
+        let mut j = json!({
+            "name": vdi.name.as_str().to_string(),
+            "scope": vdi.source_info.scope.as_usize(),
+            "place": vdi.place.to_json(ms),
+        });
+        set_span_json(ms, &mut j, "pos", vdi.source_info.span);
+        debug_info.push(j);
+    }
+
+    // This version of rustc does not record which scopes were introduced by MIR inlining, so
+    // there is no inlined-from information to report.
+    let mut scopes = Vec::with_capacity(mir.source_scopes.len());
+    for (idx, scope) in mir.source_scopes.iter_enumerated() {
+        let safety = match scope.local_data {
+            mir::ClearCrossCrate::Set(ref data) => match data.safety {
+                mir::Safety::Safe => json!("Safe"),
+                mir::Safety::BuiltinUnsafe => json!("BuiltinUnsafe"),
+                mir::Safety::FnUnsafe => json!("FnUnsafe"),
+                mir::Safety::ExplicitUnsafe(_) => json!("ExplicitUnsafe"),
+            },
+            // Scope data is cleared from MIR loaded from other crates.
+            mir::ClearCrossCrate::Clear => serde_json::Value::Null,
+        };
+        let mut j = json!({
+            "scope": idx.as_usize(),
+            "parent": scope.parent_scope.map(|p| p.as_usize()),
+            "safety": safety,
+        });
+        set_span_json(ms, &mut j, "pos", scope.span);
+        scopes.push(j);
+    }
+
     json!({
         "vars": vars,
-        "blocks": blocks
+        "blocks": blocks,
+        "var_debug_info": debug_info,
+        "source_scopes": scopes,
     })
 }
 
@@ -617,6 +865,131 @@ fn emit_trait<'tcx>(
 }
 
 
+/// Emit the generic definitions of all traits declared in the current crate, and all impls in the
+/// current crate of any trait.
+fn emit_trait_defs(ms: &mut MirState, out: &mut impl JsonOutput) -> io::Result<()> {
+    let tcx = ms.state.tcx;
+    for &trait_id in tcx.all_traits(LOCAL_CRATE).iter() {
+        if trait_id.is_local() {
+            emit_trait_def(ms, out, trait_id)?;
+        }
+        for impl_id in tcx.all_impls(trait_id) {
+            if impl_id.is_local() {
+                emit_impl(ms, out, impl_id)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn assoc_kind_str(kind: ty::AssocKind) -> &'static str {
+    match kind {
+        ty::AssocKind::Const => "Const",
+        ty::AssocKind::Method => "Method",
+        ty::AssocKind::OpaqueTy => "OpaqueTy",
+        ty::AssocKind::Type => "Type",
+    }
+}
+
+fn emit_trait_def<'tcx>(
+    ms: &mut MirState<'_, 'tcx>,
+    out: &mut impl JsonOutput,
+    trait_id: DefId,
+) -> io::Result<()> {
+    let tcx = ms.state.tcx;
+    let mut items = Vec::new();
+    for item in tcx.associated_items(trait_id).in_definition_order() {
+        items.push(json!({
+            "kind": assoc_kind_str(item.kind),
+            "item_id": item.def_id.to_json(ms),
+            "ident": item.ident.to_string(),
+            "has_default": item.defaultness.has_value(),
+        }));
+    }
+    let impls = tcx.all_impls(trait_id).map(|id| id.to_json(ms)).collect::<Vec<_>>();
+
+    out.emit(EntryKind::TraitDef, json!({
+        "name": trait_id.to_json(ms),
+        "items": items,
+        "generics": tcx.generics_of(trait_id).to_json(ms),
+        "predicates": tcx.predicates_of(trait_id).to_json(ms),
+        "impls": impls,
+    }))?;
+    emit_new_types(ms, out)
+}
+
+/// Emit an entry describing the trait impl `impl_id`.  Each item gives the trait item it
+/// implements.  Non-generic methods also give the name their `fns` entry would have (the method
+/// is only emitted if something else uses it), and associated types and consts give their
+/// (possibly generic) type and value.
+fn emit_impl<'tcx>(
+    ms: &mut MirState<'_, 'tcx>,
+    out: &mut impl JsonOutput,
+    impl_id: DefId,
+) -> io::Result<()> {
+    let tcx = ms.state.tcx;
+    let trait_ref = match tcx.impl_trait_ref(impl_id) {
+        Some(x) => x,
+        None => return Ok(()),
+    };
+
+    let trait_items = tcx.associated_items(trait_ref.def_id);
+    let mut items = Vec::new();
+    for item in tcx.associated_items(impl_id).in_definition_order() {
+        let trait_item = trait_items.in_definition_order()
+            .find(|ti| ti.kind == item.kind && ti.ident.name == item.ident.name)
+            .map(|ti| ti.def_id);
+        let mut j = json!({
+            "kind": assoc_kind_str(item.kind),
+            "item_id": item.def_id.to_json(ms),
+            "trait_item": trait_item.map(|id| id.to_json(ms)),
+        });
+        let generic = tcx.generics_of(item.def_id).requires_monomorphization(tcx);
+        // Lifetime params don't require monomorphization, but still need (erased) substs.
+        let substs = tcx.erase_regions(
+            &ty::subst::InternalSubsts::identity_for_item(tcx, item.def_id));
+        match item.kind {
+            ty::AssocKind::Method if !generic => {
+                let inst = ty::Instance::new(item.def_id, substs);
+                j["fn"] = inst_id_str(tcx, inst).into();
+            },
+            ty::AssocKind::Type => {
+                j["ty"] = tcx.type_of(item.def_id).to_json(ms);
+            },
+            ty::AssocKind::Const => {
+                let ty = tcx.type_of(item.def_id);
+                j["ty"] = ty.to_json(ms);
+                // Unused associated consts are never evaluated by rustc, so they may fail to
+                // evaluate here.  In that case, the value is omitted.
+                let val = if generic {
+                    None
+                } else {
+                    tcx.const_eval_resolve(
+                        ty::ParamEnv::reveal_all(), item.def_id, substs, None, None).ok()
+                };
+                if let Some(val) = val {
+                    let c = tcx.mk_const(ty::Const { val: ty::ConstKind::Value(val), ty });
+                    j["val"] = c.to_json(ms);
+                }
+            },
+            _ => {},
+        }
+        items.push(j);
+    }
+
+    ms.state.session.note_without_error(
+        &format!("Emitting impl {}", tcx.def_path_str(impl_id)));
+    out.emit(EntryKind::Impl, json!({
+        "name": impl_id.to_json(ms),
+        "trait_ref": trait_ref.to_json(ms),
+        "generics": tcx.generics_of(impl_id).to_json(ms),
+        "predicates": tcx.predicates_of(impl_id).to_json(ms),
+        "items": items,
+    }))?;
+    emit_new_types(ms, out)
+}
+
+
 /// Emit all statics defined in the current crate.
 fn emit_statics(ms: &mut MirState, out: &mut impl JsonOutput) -> io::Result<()> {
     let tcx = ms.state.tcx;
@@ -636,7 +1009,7 @@ fn emit_static(ms: &mut MirState, out: &mut impl JsonOutput, def_id: DefId) -> i
     let name = def_id_str(tcx, def_id);
 
     let mir = tcx.optimized_mir(def_id);
-    emit_fn(ms, out, &name, None, mir)?;
+    emit_fn(ms, out, &name, None, None, mir)?;
     emit_static_decl(ms, out, &name, mir.return_ty(), tcx.is_mutable_static(def_id))?;
 
     for (idx, mir) in tcx.promoted_mir(def_id).iter_enumerated() {
@@ -675,10 +1048,11 @@ fn init_instances(ms: &mut MirState, out: &mut impl JsonOutput) -> io::Result<()
         .any(|&(key, _)| key.as_str() == "crux_top_level");
 
     if is_top_level {
-        init_instances_from_tests(ms, out)
+        init_instances_from_tests(ms, out)?;
     } else {
-        init_instances_from_mono_items(ms)
+        init_instances_from_mono_items(ms)?;
     }
+    init_instances_from_root_names(ms, out, is_top_level)
 }
 
 /// Add every `MonoItem::Fn` to `ms.used.instances`.
@@ -703,32 +1077,452 @@ fn init_instances_from_tests(ms: &mut MirState, out: &mut impl JsonOutput) -> io
             continue;
         }
 
-        if tcx.def_kind(def_id) != Some(DefKind::Fn) {
+        add_root_fn(ms, out, def_id, "#[crux_test]")?;
+    }
+    if env_flag("CRUX_INCLUDE_TESTS") {
+        init_instances_from_libtest(ms, out)?;
+    }
+    Ok(())
+}
+
+/// Add the standard `#[test]` functions of the crate as roots.  This requires the crate to be
+/// compiled with `--test`.  The libtest harness replaces each `#[test]` attribute with a
+/// `#[rustc_test_marker]` const that has the same name as the test function, and is defined in the
+/// same module.  Tests marked `#[ignore]` are skipped.
+fn init_instances_from_libtest(ms: &mut MirState, out: &mut impl JsonOutput) -> io::Result<()> {
+    let tcx = ms.state.tcx;
+    let mut fns = HashMap::new();
+    let mut markers = Vec::new();
+    for &def_id in tcx.mir_keys(def_id::LOCAL_CRATE) {
+        match tcx.def_kind(def_id) {
+            Some(DefKind::Fn) => {
+                fns.insert((tcx.parent(def_id), tcx.item_name(def_id)), def_id);
+            },
+            Some(DefKind::Const) if tcx.has_attr(def_id, Symbol::intern("rustc_test_marker")) => {
+                markers.push(def_id);
+            },
+            _ => {},
+        }
+    }
+
+    for marker in markers {
+        let def_id = match fns.get(&(tcx.parent(marker), tcx.item_name(marker))) {
+            Some(&x) => x,
+            None => {
+                tcx.sess.span_err(tcx.def_span(marker), "failed to find function for this test");
+                continue;
+            },
+        };
+        if tcx.has_attr(def_id, Symbol::intern("ignore")) || has_test_attr(tcx, def_id) {
+            continue;
+        }
+        add_root_fn(ms, out, def_id, "#[test]")?;
+    }
+    Ok(())
+}
+
+/// Add the functions named in `CRUX_ROOTS` as roots.  The variable holds a comma-separated list of
+/// paths, such as `my_crate::module::function`.  The crate name may be omitted, in which case the
+/// path is looked up in every crate that gets compiled.
+///
+/// Each crate only adds the names it defines.  A name may match in any crate of the build, so
+/// names that matched nowhere are reported by `mir-json-rustc-wrapper` after linking.
+fn init_instances_from_root_names(
+    ms: &mut MirState,
+    out: &mut impl JsonOutput,
+    is_top_level: bool,
+) -> io::Result<()> {
+    let names = match env::var("CRUX_ROOTS") {
+        Ok(s) => s.split(',')
+            .map(|x| x.trim())
+            .filter(|x| x.len() > 0)
+            .map(|x| x.to_owned())
+            .collect::<HashSet<_>>(),
+        Err(_) => return Ok(()),
+    };
+    if names.len() == 0 {
+        return Ok(());
+    }
+
+    let tcx = ms.state.tcx;
+    for &def_id in tcx.mir_keys(def_id::LOCAL_CRATE) {
+        let qualified = qualified_def_path(tcx, def_id);
+        let path = qualified.splitn(2, "::").nth(1).unwrap_or("");
+        // `#[crux_test]` functions were already added by `init_instances_from_tests`.
+        let is_test = is_top_level && has_test_attr(tcx, def_id);
+        if (names.contains(path) || names.contains(&qualified)) && !is_test {
+            add_root_fn(ms, out, def_id, "CRUX_ROOTS")?;
+        }
+    }
+    Ok(())
+}
+
+/// Get the path of `def_id`, starting with the name of its crate, as used in `CRUX_ROOTS`.
+fn qualified_def_path(tcx: TyCtxt, def_id: DefId) -> String {
+    let path = tcx.def_path_str(def_id);
+    let path = path.trim_start_matches("crate::");
+    format!("{}::{}", tcx.crate_name(def_id.krate), path)
+}
+
+/// Add the function `def_id` as a root, after checking that it can be used as one.  `what`
+/// describes how the root was requested, for use in error messages.  Generic functions are added
+/// once for each set of type arguments listed in `#[crux_test(instantiate(...))]`.
+fn add_root_fn(
+    ms: &mut MirState,
+    out: &mut impl JsonOutput,
+    def_id: DefId,
+    what: &str,
+) -> io::Result<()> {
+    let tcx = ms.state.tcx;
+    match tcx.def_kind(def_id) {
+        Some(DefKind::Fn) |
+        Some(DefKind::AssocFn) => {},
+        _ => {
             tcx.sess.span_err(
                 tcx.def_span(def_id),
-                "#[test] can only be applied to functions",
+                &format!("{} can only be applied to functions", what),
             );
-            continue;
-        }
-        if tcx.generics_of(def_id).count() > 0 {
+            return Ok(());
+        },
+    }
+
+    let args = crux_test_args(tcx, def_id);
+    let is_generic = tcx.generics_of(def_id).count() > 0;
+
+    if args.instantiate.len() == 0 {
+        if is_generic {
+            // For associated functions, this also catches functions in generic impls.
             tcx.sess.span_err(
                 tcx.def_span(def_id),
-                "#[test] cannot be applied to generic functions",
+                &format!("{} cannot be applied to generic functions without `instantiate`", what),
             );
-            continue;
+            return Ok(());
         }
-
         let inst = ty::Instance::resolve(tcx, ty::ParamEnv::reveal_all(), def_id, List::empty())
             .unwrap_or_else(|| {
                 panic!("Instance::resolve failed to find test function {:?}?", def_id);
             });
+        return add_root_instance(ms, out, inst, &args);
+    }
 
-        ms.used.instances.insert(inst);
-        out.add_root(inst_id_str(tcx, inst))?;
+    if !is_generic {
+        tcx.sess.span_err(
+            tcx.def_span(def_id),
+            "`instantiate` can only be used on generic functions",
+        );
+        return Ok(());
+    }
+    for &(span, ref ty_names) in &args.instantiate {
+        if let Some(inst) = instantiate_root(tcx, def_id, span, ty_names) {
+            add_root_instance(ms, out, inst, &args)?;
+        }
     }
     Ok(())
 }
 
+fn add_root_instance<'tcx>(
+    ms: &mut MirState<'_, 'tcx>,
+    out: &mut impl JsonOutput,
+    inst: ty::Instance<'tcx>,
+    args: &CruxTestArgs,
+) -> io::Result<()> {
+    let tcx = ms.state.tcx;
+    let name = inst_id_str(tcx, inst);
+    let mut meta = args.to_json();
+    meta["name"] = name.clone().into();
+    // The enclosing module, or the impl for associated functions.
+    meta["parent"] = tcx.parent(inst.def_id()).map(|p| tcx.def_path_str(p)).into();
+    // The crate-qualified path of the function, used to check `CRUX_ROOTS` after linking.
+    meta["path"] = qualified_def_path(tcx, inst.def_id()).into();
+    // `name` includes a hash of the type arguments, so also give a human-readable form.
+    meta["display_name"] = inst.to_string().into();
+    out.emit(EntryKind::RootMeta, meta)?;
+
+    ms.used.instances.insert(inst);
+    out.add_root(name)?;
+    Ok(())
+}
+
+/// Resolve the instance of generic function `def_id` with the named type arguments.  Problems are
+/// reported as errors at `span`, the location of the `instantiate` argument.
+fn instantiate_root<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    def_id: DefId,
+    span: Span,
+    ty_names: &[String],
+) -> Option<ty::Instance<'tcx>> {
+    let mut tys = Vec::with_capacity(ty_names.len());
+    for name in ty_names {
+        match resolve_type_name(tcx, name) {
+            Some(ty) => tys.push(ty),
+            None => {
+                tcx.sess.span_err(span, &format!("cannot find non-generic type `{}`", name));
+                return None;
+            },
+        }
+    }
+
+    let mut n_expected = 0;
+    let mut has_const_params = false;
+    let mut generics = tcx.generics_of(def_id);
+    loop {
+        for param in &generics.params {
+            match param.kind {
+                ty::GenericParamDefKind::Lifetime => {},
+                ty::GenericParamDefKind::Type { .. } => n_expected += 1,
+                ty::GenericParamDefKind::Const => has_const_params = true,
+            }
+        }
+        match generics.parent {
+            Some(parent) => generics = tcx.generics_of(parent),
+            None => break,
+        }
+    }
+    if has_const_params {
+        tcx.sess.span_err(span, "`instantiate` does not support const generic parameters");
+        return None;
+    }
+    if tys.len() != n_expected {
+        tcx.sess.span_err(span, &format!(
+            "expected {} type arguments for `{}`, but got {}",
+            n_expected, tcx.def_path_str(def_id), tys.len(),
+        ));
+        return None;
+    }
+
+    let mut next_ty = tys.iter();
+    let substs = ty::subst::InternalSubsts::for_item(tcx, def_id, |param, _| match param.kind {
+        ty::GenericParamDefKind::Type { .. } => (*next_ty.next().unwrap()).into(),
+        _ => tcx.lifetimes.re_erased.into(),
+    });
+
+    if !tcx.substitute_normalize_and_test_predicates((def_id, substs)) {
+        tcx.sess.span_err(span, &format!(
+            "type arguments `{}` do not satisfy the bounds of `{}`",
+            ty_names.join(", "), tcx.def_path_str(def_id),
+        ));
+        return None;
+    }
+    let inst = ty::Instance::resolve(tcx, ty::ParamEnv::reveal_all(), def_id, substs);
+    if inst.is_none() {
+        tcx.sess.span_err(span, &format!(
+            "failed to resolve `{}` with type arguments `{}`",
+            tcx.def_path_str(def_id), ty_names.join(", "),
+        ));
+    }
+    inst
+}
+
+/// Look up a non-generic type by name.  This accepts primitive types, `()`, paths to types in the
+/// current crate (with or without the crate name), paths to types in other crates (starting with
+/// the crate name), and single-segment names from the standard prelude, such as `String`.
+fn resolve_type_name<'tcx>(tcx: TyCtxt<'tcx>, name: &str) -> Option<ty::Ty<'tcx>> {
+    let name = name.trim();
+    let prim = match name {
+        "()" => Some(tcx.mk_unit()),
+        "bool" => Some(tcx.types.bool),
+        "char" => Some(tcx.types.char),
+        "str" => Some(tcx.types.str_),
+        "i8" => Some(tcx.types.i8),
+        "i16" => Some(tcx.types.i16),
+        "i32" => Some(tcx.types.i32),
+        "i64" => Some(tcx.types.i64),
+        "i128" => Some(tcx.types.i128),
+        "isize" => Some(tcx.types.isize),
+        "u8" => Some(tcx.types.u8),
+        "u16" => Some(tcx.types.u16),
+        "u32" => Some(tcx.types.u32),
+        "u64" => Some(tcx.types.u64),
+        "u128" => Some(tcx.types.u128),
+        "usize" => Some(tcx.types.usize),
+        "f32" => Some(tcx.types.f32),
+        "f64" => Some(tcx.types.f64),
+        _ => None,
+    };
+    if prim.is_some() {
+        return prim;
+    }
+
+    let segs = name.trim_start_matches("::").split("::").collect::<Vec<_>>();
+    let def_id = find_local_type(tcx, name)
+        .or_else(|| find_extern_type(tcx, &segs))
+        .or_else(|| {
+            if segs.len() == 1 {
+                find_extern_type(tcx, &["std", "prelude", "v1", segs[0]])
+            } else {
+                None
+            }
+        })?;
+    if tcx.generics_of(def_id).count() > 0 {
+        return None;
+    }
+    Some(tcx.type_of(def_id))
+}
+
+fn is_type_def_kind(kind: Option<DefKind>) -> bool {
+    match kind {
+        Some(DefKind::Struct) |
+        Some(DefKind::Enum) |
+        Some(DefKind::Union) |
+        Some(DefKind::TyAlias) => true,
+        _ => false,
+    }
+}
+
+fn find_local_type(tcx: TyCtxt, name: &str) -> Option<DefId> {
+    let crate_name = tcx.crate_name.to_string();
+    for item in tcx.hir().krate().items.values() {
+        let def_id = tcx.hir().local_def_id(item.hir_id);
+        if !is_type_def_kind(tcx.def_kind(def_id)) {
+            continue;
+        }
+        let path = tcx.def_path_str(def_id);
+        let path = path.trim_start_matches("crate::");
+        if path == name || format!("{}::{}", crate_name, path) == name {
+            return Some(def_id);
+        }
+    }
+    None
+}
+
+/// Find a type in another crate by walking its module tree.  `segs[0]` is the crate name.
+fn find_extern_type(tcx: TyCtxt, segs: &[&str]) -> Option<DefId> {
+    let (&krate_name, rest) = segs.split_first()?;
+    if rest.len() == 0 {
+        return None;
+    }
+    let &cnum = tcx.crates().iter()
+        .find(|&&cnum| tcx.crate_name(cnum).as_str() == krate_name)?;
+    let mut cur = DefId { krate: cnum, index: def_id::CRATE_DEF_INDEX };
+    for (i, &seg) in rest.iter().enumerate() {
+        let is_last = i == rest.len() - 1;
+        let child = tcx.item_children(cur).iter().find(|export| {
+            if export.ident.as_str() != seg {
+                return false;
+            }
+            match export.res {
+                Res::Def(DefKind::Mod, _) => !is_last,
+                Res::Def(kind, _) => is_last && is_type_def_kind(Some(kind)),
+                _ => false,
+            }
+        })?;
+        cur = child.res.def_id();
+    }
+    Some(cur)
+}
+
+/// Arguments of the `#[crux_test]` attribute, along with other per-test settings taken from the
+/// function's attributes.
+#[derive(Default)]
+struct CruxTestArgs {
+    should_fail: bool,
+    /// Set by `#[should_panic]`, for standard `#[test]` functions.  The inner `Option` holds the
+    /// `expected` message, if any.
+    should_panic: Option<Option<String>>,
+    path_sat: bool,
+    timeout: Option<u64>,
+    solver: Option<String>,
+    /// Type arguments for each requested instantiation of a generic test, along with the span of
+    /// the argument that requested it.
+    instantiate: Vec<(Span, Vec<String>)>,
+}
+
+impl CruxTestArgs {
+    /// Build the per-root metadata for a test with these arguments.
+    fn to_json(&self) -> serde_json::Value {
+        json!({
+            "should_fail": self.should_fail,
+            "should_panic": self.should_panic.is_some(),
+            "should_panic_expected": self.should_panic.as_ref().and_then(|x| x.as_ref()),
+            "path_sat": self.path_sat,
+            "timeout": self.timeout,
+            "solver": self.solver,
+        })
+    }
+}
+
+/// Parse the arguments of the `#[crux_test]` attribute on `def_id`.  Supported arguments are
+/// `should_fail`, `path_sat`, `timeout = N` (in seconds), `solver = "name"`, and
+/// `instantiate(T1, T2, ...)`.  Each argument of `instantiate` is one instantiation: either a
+/// type path, for functions with a single type parameter, or a string containing a
+/// comma-separated list of type paths.  Invalid arguments are reported as errors.
+fn crux_test_args(tcx: TyCtxt, def_id: DefId) -> CruxTestArgs {
+    let mut args = CruxTestArgs::default();
+
+    for attr in tcx.get_attrs(def_id).iter() {
+        if attr.check_name(Symbol::intern("should_panic")) {
+            // Either `#[should_panic = "msg"]` or `#[should_panic(expected = "msg")]`.
+            let expected = attr.value_str().or_else(|| {
+                attr.meta_item_list()?.iter()
+                    .find(|nested| nested.check_name(Symbol::intern("expected")))?
+                    .value_str()
+            });
+            args.should_panic = Some(expected.map(|s| s.to_string()));
+            continue;
+        }
+        if !attr.check_name(Symbol::intern("crux_test")) {
+            continue;
+        }
+        // `meta_item_list` is `None` for a bare `#[crux_test]`.
+        for nested in attr.meta_item_list().unwrap_or_default() {
+            let mi = match nested.meta_item() {
+                Some(mi) => mi,
+                None => {
+                    tcx.sess.span_err(
+                        nested.span(), "expected a crux_test argument, not a literal");
+                    continue;
+                },
+            };
+            match &*mi.name_or_empty().as_str() {
+                "should_fail" if mi.is_word() => args.should_fail = true,
+                "path_sat" if mi.is_word() => args.path_sat = true,
+                "timeout" if args.timeout.is_some() => {
+                    tcx.sess.span_err(mi.span, "duplicate crux_test argument `timeout`");
+                },
+                "timeout" => match mi.name_value_literal().map(|lit| &lit.kind) {
+                    Some(&ast::LitKind::Int(n, _)) if n <= u64::max_value() as u128 => {
+                        args.timeout = Some(n as u64);
+                    },
+                    Some(&ast::LitKind::Int(..)) => {
+                        tcx.sess.span_err(mi.span, "timeout is too large");
+                    },
+                    _ => tcx.sess.span_err(mi.span, "expected `timeout = <seconds>`"),
+                },
+                "solver" if args.solver.is_some() => {
+                    tcx.sess.span_err(mi.span, "duplicate crux_test argument `solver`");
+                },
+                "solver" => match mi.value_str() {
+                    Some(s) => args.solver = Some(s.to_string()),
+                    None => tcx.sess.span_err(mi.span, "expected `solver = \"<name>\"`"),
+                },
+                "instantiate" => match mi.meta_item_list() {
+                    Some(items) => for item in items {
+                        if let Some(ty_mi) = item.meta_item().filter(|m| m.is_word()) {
+                            let segs = ty_mi.path.segments.iter()
+                                .map(|seg| seg.ident.to_string())
+                                .collect::<Vec<_>>();
+                            args.instantiate.push((item.span(), vec![segs.join("::")]));
+                        } else if let Some(&ast::LitKind::Str(s, _)) =
+                                item.literal().map(|lit| &lit.kind) {
+                            let tys = s.as_str().split(',').map(|x| x.trim().to_owned()).collect();
+                            args.instantiate.push((item.span(), tys));
+                        } else {
+                            tcx.sess.span_err(item.span(), "expected a type path or string");
+                        }
+                    },
+                    None => tcx.sess.span_err(mi.span, "expected `instantiate(<types>)`"),
+                },
+                "should_fail" | "path_sat" => {
+                    tcx.sess.span_err(mi.span, "this crux_test argument takes no value");
+                },
+                _ => tcx.sess.span_err(mi.span, "unknown crux_test argument"),
+            }
+        }
+    }
+
+    args
+}
+
 
 /// Add a single `Instance` to `out.fns` and/or `out.intrinsics`, depending on its kind.
 fn emit_instance<'tcx>(
@@ -778,13 +1572,15 @@ fn emit_instance<'tcx>(
     let mir: Body = tcx.subst_and_normalize_erasing_regions(
         inst.substs, ty::ParamEnv::reveal_all(), &mir as &Body);
     let mir = tcx.arena.alloc(mir::BodyAndCache::new(mir));
-    emit_fn(ms, out, &name, Some(inst), mir)?;
+    mir.ensure_predecessors();
+    emit_fn(ms, out, &name, Some(inst), None, mir)?;
 
     if let ty::InstanceDef::Item(def_id) = inst.def {
         for (idx, mir) in tcx.promoted_mir(def_id).iter_enumerated() {
             let mir = tcx.subst_and_normalize_erasing_regions(
                 inst.substs, ty::ParamEnv::reveal_all(), mir);
             let mir = tcx.arena.alloc(mir);
+            mir.ensure_predecessors();
             emit_promoted(ms, out, &name, idx, mir)?;
         }
     }
@@ -792,15 +1588,42 @@ fn emit_instance<'tcx>(
     Ok(())
 }
 
+/// Emit the generic MIR of every local function that requires monomorphization, along with its
+/// generics and where-clauses.  These entries are named after the generic item itself, and don't
+/// add any of the functions they call to `ms.used`.
+fn emit_generic_fns<'tcx>(
+    ms: &mut MirState<'_, 'tcx>,
+    out: &mut impl JsonOutput,
+) -> io::Result<()> {
+    let tcx = ms.state.tcx;
+    for &def_id in tcx.mir_keys(LOCAL_CRATE) {
+        let is_fn = match tcx.def_kind(def_id) {
+            Some(DefKind::Fn) | Some(DefKind::AssocFn) => true,
+            _ => tcx.is_closure(def_id),
+        };
+        if !is_fn || !tcx.generics_of(def_id).requires_monomorphization(tcx) {
+            continue;
+        }
+
+        let name = def_id_str(tcx, def_id);
+        let mir = tcx.optimized_mir(def_id);
+        emit_fn(ms, out, &name, None, Some(def_id), mir)?;
+        for (idx, mir) in tcx.promoted_mir(def_id).iter_enumerated() {
+            emit_promoted(ms, out, &name, idx, mir)?;
+        }
+    }
+    Ok(())
+}
+
 fn emit_promoted<'tcx>(
     ms: &mut MirState<'_, 'tcx>,
     out: &mut impl JsonOutput,
     parent: &str,
     idx: mir::Promoted,
-    mir: &'tcx Body<'tcx>,
+    mir: &'tcx mir::BodyAndCache<'tcx>,
 ) -> io::Result<()> {
     let name = format!("{}::{{{{promoted}}}}[{}]", parent, idx.as_usize());
-    emit_fn(ms, out, &name, None, mir)?;
+    emit_fn(ms, out, &name, None, None, mir)?;
     emit_static_decl(ms, out, &name, mir.return_ty(), false)?;
     Ok(())
 }
@@ -868,13 +1691,95 @@ fn emit_adt<'tcx>(
 }
 
 
-/// Output a MIR body to `out.fns`.  Recursively emits all promoted statics from the body.
+/// Add a memory allocation to `out.allocs`.  Allocations for statics and functions are not
+/// emitted here, since those are described by the `statics` and `fns` tables instead.
+fn emit_alloc<'tcx>(
+    ms: &mut MirState<'_, 'tcx>,
+    out: &mut impl JsonOutput,
+    id: mir::interpret::AllocId,
+) -> io::Result<()> {
+    let tcx = ms.state.tcx;
+    let ga = tcx.alloc_map.lock().get(id);
+    let alloc = match ga {
+        Some(mir::interpret::GlobalAlloc::Memory(alloc)) => alloc,
+        _ => return Ok(()),
+    };
+
+    let size = alloc.len();
+    let bytes = alloc.inspect_with_undef_and_ptr_outside_interpreter(0 .. size);
+
+    // Ranges of bytes that are initialized, as `[start, end)` pairs.
+    let mut defined = Vec::new();
+    let mut start = None;
+    for i in 0 .. size {
+        let def = alloc.undef_mask().get(Size::from_bytes(i as u64));
+        match (def, start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                defined.push(json!([s, i]));
+                start = None;
+            },
+            _ => {},
+        }
+    }
+    if let Some(s) = start {
+        defined.push(json!([s, size]));
+    }
+
+    // Each relocation covers a pointer-sized range of `bytes` starting at `offset`.  The bytes
+    // themselves hold the offset of the pointer within its target allocation.
+    let mut relocations = Vec::with_capacity(alloc.relocations().len());
+    for &(offset, ((), target)) in alloc.relocations().iter() {
+        relocations.push(json!({
+            "offset": offset.bytes(),
+            "target": alloc_target_json(ms, target),
+        }));
+    }
+
+    out.emit(EntryKind::Alloc, json!({
+        "name": alloc_id_str(tcx, id),
+        "bytes": bytes,
+        "defined": defined,
+        "align": alloc.align.bytes(),
+        "mutable": alloc.mutability == ast::Mutability::Mut,
+        "relocations": relocations,
+    }))?;
+    Ok(())
+}
+
+/// Add a source file to `out.sources`.  `pos` may be any position within the file.
+fn emit_source(ms: &mut MirState, out: &mut impl JsonOutput, pos: BytePos) -> io::Result<()> {
+    let source_map = ms.state.session.source_map();
+    let file = source_map.lookup_source_file(pos);
+
+    let mut j = json!({
+        "name": source_file_id_str(&file),
+        "path": file.name.to_string(),
+        "size": (file.end_pos - file.start_pos).0,
+        // This is rustc's 128-bit SipHash (with zero keys) of the file's contents.
+        "src_hash": format!("{:032x}", file.src_hash),
+    });
+    if ms.state.opts.source_contents {
+        // Sources of files from other crates are loaded lazily.
+        source_map.ensure_source_file_source_present(file.clone());
+        let src = file.src.as_ref().map(|s| s.to_string())
+            .or_else(|| file.external_src.borrow().get_source().map(|s| s.to_string()));
+        j["contents"] = src.into();
+    }
+    out.emit(EntryKind::Source, j)?;
+    Ok(())
+}
+
+
+/// Output a MIR body to `out.fns`, or to `out.generic_fns` if it's the generic body of
+/// `generic_def`.  Recursively emits all promoted statics from the body.
 fn emit_fn<'tcx>(
     ms: &mut MirState<'_, 'tcx>,
     out: &mut impl JsonOutput,
     name: &str,
     inst: Option<ty::Instance<'tcx>>,
-    mir: &'tcx Body<'tcx>,
+    generic_def: Option<DefId>,
+    mir: &'tcx mir::BodyAndCache<'tcx>,
 ) -> io::Result<()> {
     ms.state.session.note_without_error(&format!("Emitting MIR for {}", name));
 
@@ -884,22 +1789,75 @@ fn emit_fn<'tcx>(
         state: ms.state,
         tys: ms.tys,
         match_span_map: ms.match_span_map,
+        alloc_ids: ms.alloc_ids,
     };
     let ms = &mut ms;
 
     let abi = inst.map(|i| inst_abi(ms.state.tcx, i)).unwrap_or(abi::Abi::Rust);
 
-    out.emit(EntryKind::Fn, json!({
+    let mut j = json!({
         "name": &name,
         "args": mir.args_iter().map(|l| local_json(ms, l)).collect::<Vec<_>>(),
         "return_ty": mir.return_ty().to_json(ms),
         "body": mir_body(ms),
         "abi": abi.to_json(ms),
         "spread_arg": mir.spread_arg.map(|x| x.as_usize()),
-    }))?;
+    });
+    if ms.state.opts.cfg {
+        j["cfg"] = cfg::cfg_json(mir, ms.state.opts.panic_abort);
+    }
+    let item_def_id = match inst.map(|i| i.def) {
+        Some(ty::InstanceDef::Item(def_id)) => Some(def_id),
+        _ => generic_def,
+    };
+    if let Some(def_id) = item_def_id {
+        j["flags"] = fn_flags_json(ms.state.tcx, def_id);
+        j["attrs"] = attr_paths_json(ms.state.tcx, def_id);
+    }
+    let kind = match generic_def {
+        Some(def_id) => {
+            j["generics"] = ms.state.tcx.generics_of(def_id).to_json(ms);
+            j["predicates"] = ms.state.tcx.predicates_of(def_id).to_json(ms);
+            EntryKind::GenericFn
+        },
+        None => EntryKind::Fn,
+    };
+    out.emit(kind, j)?;
     emit_new_types(ms, out)
 }
 
+/// Describe the properties of function `def_id` that come from its signature and attributes.
+fn fn_flags_json(tcx: TyCtxt, def_id: DefId) -> serde_json::Value {
+    let attrs = tcx.codegen_fn_attrs(def_id);
+    // `fn_sig` is not available for closures, which are never `unsafe`.
+    let is_unsafe = !tcx.is_closure(def_id) &&
+        tcx.fn_sig(def_id).unsafety() == hir::Unsafety::Unsafe;
+    json!({
+        "unsafe": is_unsafe,
+        "const": tcx.is_const_fn_raw(def_id),
+        "inline": format!("{:?}", attrs.inline),
+        "cold": attrs.flags.contains(CodegenFnAttrFlags::COLD),
+        "no_mangle": attrs.flags.contains(CodegenFnAttrFlags::NO_MANGLE),
+        "track_caller": attrs.flags.contains(CodegenFnAttrFlags::TRACK_CALLER),
+        "should_panic": tcx.has_attr(def_id, Symbol::intern("should_panic")),
+    })
+}
+
+/// List the paths of all attributes on `def_id`, including tool attributes such as
+/// `rustfmt::skip`.  Doc comments are omitted.
+fn attr_paths_json(tcx: TyCtxt, def_id: DefId) -> serde_json::Value {
+    let mut paths = Vec::new();
+    for attr in tcx.get_attrs(def_id).iter() {
+        if let ast::AttrKind::Normal(ref item) = attr.kind {
+            let segs = item.path.segments.iter()
+                .map(|seg| seg.ident.to_string())
+                .collect::<Vec<_>>();
+            paths.push(segs.join("::"));
+        }
+    }
+    paths.into()
+}
+
 fn emit_new_types(
     ms: &mut MirState,
     out: &mut impl JsonOutput,
@@ -983,9 +1941,11 @@ fn analyze_inner<'tcx, O: JsonOutput, F: FnOnce(&Path) -> io::Result<O>>(
 
         let mut used = Used::default();
         let mut tys = TyIntern::default();
+        let mut alloc_ids = HashMap::new();
         let state = CompileState {
             session: sess,
             tcx,
+            opts: ExportOptions::from_env(),
         };
         let mut ms = MirState {
             mir: None,
@@ -993,14 +1953,21 @@ fn analyze_inner<'tcx, O: JsonOutput, F: FnOnce(&Path) -> io::Result<O>>(
             state: &state,
             tys: &mut tys,
             match_span_map: &get_match_spans(),
+            alloc_ids: &mut alloc_ids,
         };
 
         // Traits and top-level statics can be enumerated directly.
         emit_statics(&mut ms, &mut out)?;
+        if ms.state.opts.trait_defs {
+            emit_trait_defs(&mut ms, &mut out)?;
+        }
 
         // Everything else is demand-driven, to handle monomorphization.  We start with all
         // #[test] functions, then keep looping until there are no more nodes to process.
         init_instances(&mut ms, &mut out)?;
+        if ms.state.opts.polymorphic {
+            emit_generic_fns(&mut ms, &mut out)?;
+        }
 
         while ms.used.has_new() {
             for inst in ms.used.instances.take_new() {
@@ -1015,6 +1982,12 @@ fn analyze_inner<'tcx, O: JsonOutput, F: FnOnce(&Path) -> io::Result<O>>(
             for ti in ms.used.traits.take_new() {
                 emit_trait(&mut ms, &mut out, ti)?;
             }
+            for id in ms.used.allocs.take_new() {
+                emit_alloc(&mut ms, &mut out, id)?;
+            }
+            for pos in ms.used.sources.take_new() {
+                emit_source(&mut ms, &mut out, pos)?;
+            }
         }
 
         // Any referenced types should normally be emitted immediately after the entry that
@@ -1045,7 +2018,8 @@ pub fn analyze_nonstreaming<'tcx>(
     };
 
     let total_items = out.fns.len() + out.adts.len() + out.statics.len() + out.vtables.len() +
-        out.traits.len() + out.intrinsics.len();
+        out.traits.len() + out.intrinsics.len() + out.allocs.len() + out.sources.len() +
+        out.root_meta.len() + out.trait_defs.len() + out.impls.len() + out.generic_fns.len();
     let j = json!({
         "fns": out.fns,
         "adts": out.adts,
@@ -1054,6 +2028,12 @@ pub fn analyze_nonstreaming<'tcx>(
         "traits": out.traits,
         "intrinsics": out.intrinsics,
         "tys": out.tys,
+        "allocs": out.allocs,
+        "sources": out.sources,
+        "root_meta": out.root_meta,
+        "trait_defs": out.trait_defs,
+        "impls": out.impls,
+        "generic_fns": out.generic_fns,
         "roots": out.roots,
     });
     sess.note_without_error(
@@ -1,10 +1,12 @@
 use rustc_hir::def_id::DefId;
 use rustc::mir::Body;
+use rustc::mir::interpret::{AllocId, Allocation};
 use rustc_session::Session;
 use rustc::ty::{self, TyCtxt};
-use rustc_span::Span;
+use rustc_span::{BytePos, Span};
 use rustc_span::symbol::Symbol;
 use serde_json;
+use lib_util::env_flag;
 use std::collections::BTreeMap;
 use std::collections::{HashMap, HashSet, hash_map};
 use std::hash::{Hash, Hasher};
@@ -14,6 +16,50 @@ use std::mem;
 pub struct CompileState<'a, 'tcx> {
     pub session: &'a Session,
     pub tcx: TyCtxt<'tcx>,
+    pub opts: ExportOptions,
+}
+
+/// Optional extensions to the output format.  These are all disabled by default.  Each option is
+/// enabled by setting the corresponding environment variable to a value other than `0`.
+#[derive(Clone, Debug, Default)]
+pub struct ExportOptions {
+    /// `MIR_JSON_STRUCTURED_SPANS`: emit spans as objects giving the file, line/column and byte
+    /// positions, and the full macro expansion backtrace, instead of as formatted strings.
+    pub structured_spans: bool,
+    /// `MIR_JSON_SOURCE_CONTENTS`: include the full text of each file in the `sources` table.
+    pub source_contents: bool,
+    /// `MIR_JSON_POLYMORPHIC`: also export the generic MIR of each local function that requires
+    /// monomorphization, with its generics and where-clauses, in the `generic_fns` table.  Types
+    /// in these bodies may contain `Param` and `Projection`.  The same variable makes `link` keep
+    /// these entries, along with everything they mention.
+    pub polymorphic: bool,
+    /// `MIR_JSON_PANIC_ABORT`: export bodies with `-C panic=abort` semantics, omitting cleanup
+    /// blocks and the `unwind`/`cleanup` edges of terminators.
+    pub panic_abort: bool,
+    /// `MIR_JSON_CFG`: add a `cfg` section to each function, giving the predecessors and
+    /// immediate dominator of each block, back edges, and natural loops.
+    pub cfg: bool,
+    /// `MIR_JSON_CONST_SUBSTS`: emit const generic arguments in `substs` as rendered constants,
+    /// instead of the `"nonty::Const"` placeholder.
+    pub const_substs: bool,
+    /// `MIR_JSON_TRAIT_DEFS`: emit the `trait_defs` and `impls` tables, describing the generic
+    /// definitions of local traits and the trait impls in this crate.  The same variable makes
+    /// `link` keep these entries, along with any impl methods they name.
+    pub trait_defs: bool,
+}
+
+impl ExportOptions {
+    pub fn from_env() -> ExportOptions {
+        ExportOptions {
+            structured_spans: env_flag("MIR_JSON_STRUCTURED_SPANS"),
+            source_contents: env_flag("MIR_JSON_SOURCE_CONTENTS"),
+            polymorphic: env_flag("MIR_JSON_POLYMORPHIC"),
+            panic_abort: env_flag("MIR_JSON_PANIC_ABORT"),
+            cfg: env_flag("MIR_JSON_CFG"),
+            const_substs: env_flag("MIR_JSON_CONST_SUBSTS"),
+            trait_defs: env_flag("MIR_JSON_TRAIT_DEFS"),
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -61,15 +107,22 @@ pub struct Used<'tcx> {
     pub vtables: UsedSet<ty::PolyTraitRef<'tcx>>,
     pub instances: UsedSet<ty::Instance<'tcx>>,
     pub traits: UsedSet<TraitInst<'tcx>>,
+    pub allocs: UsedSet<AllocId>,
+    /// Source files mentioned by spans, identified by the position of the start of the file.
+    pub sources: UsedSet<BytePos>,
 }
 
 impl<'tcx> Used<'tcx> {
     pub fn has_new(&self) -> bool {
-        let Used { ref types, ref vtables, ref instances, ref traits } = *self;
+        let Used {
+            ref types, ref vtables, ref instances, ref traits, ref allocs, ref sources,
+        } = *self;
         types.has_new() ||
         vtables.has_new() ||
         instances.has_new() ||
-        traits.has_new()
+        traits.has_new() ||
+        allocs.has_new() ||
+        sources.has_new()
     }
 }
 
@@ -231,12 +284,21 @@ impl<'tcx> TyIntern<'tcx> {
         self.map.get(&ty).map(|x| x as &str)
     }
 
-    pub fn insert(&mut self, ty: ty::Ty<'tcx>, j: serde_json::Value) -> String {
+    /// Intern a new type.  The fields of `info` (such as `layout`) are stored alongside the type's
+    /// description, but do not contribute to its unique ID.  This is for information that is
+    /// determined by the type itself, or that may not be available in every crate that mentions
+    /// the type.
+    pub fn insert(
+        &mut self,
+        ty: ty::Ty<'tcx>,
+        j: serde_json::Value,
+        info: serde_json::Map<String, serde_json::Value>,
+    ) -> String {
         let id = ty_unique_id(ty, &j);
-        self.new_vals.push(json!({
-            "name": &id,
-            "ty": j,
-        }));
+        let mut entry = info;
+        entry.insert("name".to_owned(), json!(&id));
+        entry.insert("ty".to_owned(), j);
+        self.new_vals.push(entry.into());
         let old = self.map.insert(ty, id.clone());
         assert!(old.is_none(), "duplicate insert for type {:?}", ty);
         id
@@ -263,6 +325,9 @@ pub struct MirState<'a, 'tcx : 'a> {
     /// rewritten.  This seems okay for now since the user is mostly interested in coverage in
     /// their own top-level crate anyway.
     pub match_span_map: &'a HashMap<Span, Span>,
+    /// `AllocId`s assigned to the allocations of evaluated constants, keyed on the (interned)
+    /// allocation's address.  See `ty_json::const_alloc_id`.
+    pub alloc_ids: &'a mut HashMap<*const Allocation, AllocId>,
 }
 
 /// Trait for converting MIR elements to JSON.
@@ -3,10 +3,14 @@ use rustc_hir::def_id::DefId;
 use rustc::mir;
 use rustc_mir::interpret;
 use rustc::ty;
+use rustc::ty::layout;
+use rustc::ty::subst::Subst;
+use rustc::ty::util::IntTypeExt;
 use rustc::ty::{TyCtxt, TypeFoldable};
 use rustc::ich::StableHashingContext;
 use rustc_index::vec::{IndexVec, Idx};
 use rustc_data_structures::stable_hasher::{HashStable, StableHasher};
+use rustc_target::abi::Size;
 use rustc_target::spec::abi;
 use rustc_ast::ast;
 use rustc_span::DUMMY_SP;
@@ -33,9 +37,11 @@ basic_json_enum_impl!(ast::FloatTy);
 basic_json_enum_impl!(ast::IntTy);
 basic_json_enum_impl!(ast::UintTy);
 basic_json_enum_impl!(hir::Mutability);
+basic_json_enum_impl!(hir::Movability);
 basic_json_enum_impl!(hir::def::CtorKind);
 basic_json_enum_impl!(mir::CastKind);
 basic_json_enum_impl!(abi::Abi);
+basic_json_enum_impl!(ty::ClosureKind);
 
 impl ToJson<'_> for mir::BorrowKind {
     fn to_json(&self, _mir: &mut MirState) -> serde_json::Value {
@@ -98,12 +104,26 @@ where T: HashStable<StableHashingContext<'tcx>> {
     format!("{}::{}{:016x}[0]", base, prefix, hash)
 }
 
+/// Evaluate all const generic arguments in `substs`.  Without this, two instances whose substs
+/// differ only in an unevaluated const argument (`{N + 1}` vs. `4`) would hash differently even
+/// when the values are the same.
+fn eval_const_substs<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    substs: ty::subst::SubstsRef<'tcx>,
+) -> ty::subst::SubstsRef<'tcx> {
+    tcx.mk_substs(substs.iter().map(|&arg| match arg.unpack() {
+        ty::subst::GenericArgKind::Const(c) => c.eval(tcx, ty::ParamEnv::reveal_all()).into(),
+        _ => arg,
+    }))
+}
+
 pub fn adt_inst_id_str<'tcx>(
     tcx: TyCtxt<'tcx>,
     ai: AdtInst<'tcx>,
 ) -> String {
     // Erase all early-bound regions.
     let substs = tcx.erase_regions(&ai.substs);
+    let substs = eval_const_substs(tcx, substs);
     ext_def_id_str(tcx, ai.def_id(), "_adt", substs)
 }
 
@@ -117,6 +137,7 @@ pub fn inst_id_str<'tcx>(
     );
     assert!(!substs.has_erasable_regions());
     assert!(!substs.needs_subst());
+    let substs = eval_const_substs(tcx, substs);
 
     match inst.def {
         ty::InstanceDef::Item(def_id) |
@@ -163,6 +184,12 @@ pub fn get_fn_def_name<'tcx>(
     defid: DefId,
     substs: ty::subst::SubstsRef<'tcx>,
 ) -> String {
+    // Calls from generic bodies (exported in polymorphic mode) can't be resolved to a particular
+    // instance.  These refer to the generic item instead.
+    if substs.needs_subst() {
+        return def_id_str(mir.state.tcx, defid);
+    }
+
     let inst = ty::Instance::resolve(
         mir.state.tcx,
         ty::ParamEnv::reveal_all(),
@@ -197,6 +224,46 @@ pub fn get_promoted_name<'tcx>(
     format!("{}::{{{{promoted}}}}[{}]", parent, idx.as_usize())
 }
 
+/// Get the name of a memory allocation.  Allocation IDs are only meaningful within the current
+/// compilation session, so the name is qualified with the current crate.
+pub fn alloc_id_str(tcx: TyCtxt, id: interpret::AllocId) -> String {
+    let krate = DefId::local(hir::def_id::CRATE_DEF_INDEX);
+    format!("{}::{{{{alloc}}}}[{}]", def_id_str(tcx, krate), id.0)
+}
+
+/// Describe the target of a pointer into global allocation `id`.  As a side effect, this marks
+/// the target as "used", so it will be emitted too.
+pub fn alloc_target_json<'tcx>(
+    mir: &mut MirState<'_, 'tcx>,
+    id: interpret::AllocId,
+) -> serde_json::Value {
+    let tcx = mir.state.tcx;
+    let ga = tcx.alloc_map.lock().get(id);
+    match ga {
+        Some(interpret::GlobalAlloc::Memory(_)) => {
+            mir.used.allocs.insert(id);
+            json!({
+                "kind": "alloc",
+                "def_id": alloc_id_str(tcx, id),
+            })
+        },
+        Some(interpret::GlobalAlloc::Static(def_id)) => json!({
+            "kind": "static",
+            "def_id": def_id.to_json(mir),
+        }),
+        Some(interpret::GlobalAlloc::Function(inst)) => {
+            mir.used.instances.insert(inst);
+            json!({
+                "kind": "fn",
+                "def_id": inst_id_str(tcx, inst),
+            })
+        },
+        None => json!({
+            "kind": "dangling",
+        }),
+    }
+}
+
 pub fn get_drop_fn_name<'tcx>(
     mir: &mut MirState<'_, 'tcx>,
     ty: ty::Ty<'tcx>,
@@ -206,10 +273,70 @@ pub fn get_drop_fn_name<'tcx>(
         // `None` instead of a `Ty` indicates this drop glue is a no-op.
         return None;
     }
+    if inst.substs.needs_subst() {
+        // Drop glue for a type mentioning generic parameters can't be generated until the
+        // parameters are known.  We name it, but don't emit it.
+        return Some(ext_def_id_str(mir.state.tcx, inst.def_id(), "_drop", inst.substs));
+    }
     mir.used.instances.insert(inst);
     Some(inst_id_str(mir.state.tcx, inst))
 }
 
+/// Describe closure `def_id`: its kind, parent function, signature, and captured variables.
+/// Since captures are only known in the defining crate, this is kept out of the type's hashed
+/// description.
+fn closure_info_json<'tcx>(
+    mir: &mut MirState<'_, 'tcx>,
+    def_id: DefId,
+    substs: ty::subst::SubstsRef<'tcx>,
+) -> serde_json::Value {
+    let tcx = mir.state.tcx;
+    let closure_substs = substs.as_closure();
+    json!({
+        "closure_kind": closure_substs.kind(def_id, tcx).to_json(mir),
+        "parent": tcx.closure_base_def_id(def_id).to_json(mir),
+        "signature": closure_substs.sig(def_id, tcx).to_json(mir),
+        "upvars": closure_upvars_json(mir, def_id),
+    })
+}
+
+/// Describe the variables captured by closure `def_id`, in the same order as its `upvar_tys`.
+/// Capture information is only available for closures defined in the current crate; for others,
+/// this returns `null`.
+fn closure_upvars_json(mir: &mut MirState, def_id: DefId) -> serde_json::Value {
+    let tcx = mir.state.tcx;
+    if !def_id.is_local() {
+        return serde_json::Value::Null;
+    }
+    let upvars = match tcx.upvars(def_id) {
+        Some(x) => x,
+        None => return json!([]),
+    };
+    let tables = tcx.typeck_tables_of(def_id);
+    let mut j = Vec::with_capacity(upvars.len());
+    for (&var_hir_id, upvar) in upvars.iter() {
+        let upvar_id = ty::UpvarId {
+            var_path: ty::UpvarPath { hir_id: var_hir_id },
+            closure_expr_id: hir::def_id::LocalDefId::from_def_id(def_id),
+        };
+        let mode = match tables.upvar_capture(upvar_id) {
+            ty::UpvarCapture::ByValue => "ByValue",
+            ty::UpvarCapture::ByRef(ref borrow) => match borrow.kind {
+                ty::BorrowKind::ImmBorrow => "ByRef",
+                ty::BorrowKind::UniqueImmBorrow => "ByUniqueRef",
+                ty::BorrowKind::MutBorrow => "ByMutRef",
+            },
+        };
+        let mut upvar_j = json!({
+            "name": tcx.hir().name(var_hir_id).as_str().to_string(),
+            "mode": mode,
+        });
+        super::set_span_json(mir, &mut upvar_j, "pos", upvar.span);
+        j.push(upvar_j);
+    }
+    j.into()
+}
+
 impl ToJson<'_> for hir::def_id::DefId {
     fn to_json(&self, mir: &mut MirState) -> serde_json::Value {
         json!(def_id_str(mir.state.tcx, *self))
@@ -401,36 +528,59 @@ impl<'tcx> ToJson<'tcx> for ty::Ty<'tcx> {
             }
             &ty::TyKind::FnDef(defid, ref substs) => {
                 let name = get_fn_def_name(mir, defid, substs);
+                if substs.needs_subst() {
+                    // `name` refers to the generic item, so the substs are needed to tell
+                    // different uses apart.
+                    json!({
+                        "kind": "FnDef",
+                        "defid": name,
+                        "substs": substs.to_json(mir),
+                    })
+                } else {
+                    json!({
+                        "kind": "FnDef",
+                        "defid": name,
+                    })
+                }
+            }
+            &ty::TyKind::Param(ref p) => {
+                // Only appears in generic bodies, exported in polymorphic mode.
                 json!({
-                    "kind": "FnDef",
-                    "defid": name,
+                    "kind": "Param",
+                    "param": p.to_json(mir),
+                    "name": p.name.as_str().to_string(),
                 })
             }
-            &ty::TyKind::Param(..) => unreachable!(
-                "no TyKind::Param should remain after monomorphization"
-            ),
             &ty::TyKind::Closure(defid, ref substs) => {
                 json!({
                     "kind": "Closure",
                     "upvar_tys": substs.as_closure().upvar_tys(defid, mir.state.tcx)
                         .collect::<Vec<_>>().to_json(mir),
                     // mir-verifier uses the same representation for closures as it does for
-                    // tuples, so no additional information is needed.
+                    // tuples, so no additional information is needed.  Informational details are
+                    // stored in the `closure` field of the type's entry instead.
                 })
             }
             &ty::TyKind::Dynamic(ref preds, _region) => {
                 let ti = TraitInst::from_dynamic_predicates(mir.state.tcx, *preds);
                 let trait_name = trait_inst_id_str(mir.state.tcx, &ti);
-                mir.used.traits.insert(ti);
+                // A generic trait object type has no concrete vtable layout to emit.
+                if !preds.needs_subst() {
+                    mir.used.traits.insert(ti);
+                }
                 json!({
                     "kind": "Dynamic",
                     "trait_id": trait_name,
                     "predicates": preds.skip_binder().to_json(mir),
                 })
             }
-            &ty::TyKind::Projection(..) => unreachable!(
-                "no TyKind::Projection should remain after monomorphization"
-            ),
+            &ty::TyKind::Projection(ref proj) => {
+                // Only appears in generic bodies, exported in polymorphic mode.
+                json!({
+                    "kind": "Projection",
+                    "projection": proj.to_json(mir),
+                })
+            }
             &ty::TyKind::UnnormalizedProjection(..) => unreachable!(
                 "no TyKind::UnnormalizedProjection should remain after monomorphization"
             ),
@@ -455,29 +605,213 @@ impl<'tcx> ToJson<'tcx> for ty::Ty<'tcx> {
                 // TODO
                 json!({"kind": "Placeholder"})
             }
-            &ty::TyKind::Foreign(_) => {
-                // TODO
-                json!({"kind": "Foreign"})
+            &ty::TyKind::Foreign(defid) => {
+                json!({
+                    "kind": "Foreign",
+                    "def_id": defid.to_json(mir),
+                })
             }
-            &ty::TyKind::Generator(_, _, _) => {
-                // TODO
-                json!({"kind": "Generator"})
+            &ty::TyKind::Generator(defid, ref substs, movability) => {
+                let tcx = mir.state.tcx;
+                let gen = substs.as_generator();
+                json!({
+                    "kind": "Generator",
+                    "def_id": defid.to_json(mir),
+                    "movability": movability.to_json(mir),
+                    "upvar_tys": gen.upvar_tys(defid, tcx).collect::<Vec<_>>().to_json(mir),
+                    "resume_ty": gen.resume_ty(defid, tcx).to_json(mir),
+                    "yield_ty": gen.yield_ty(defid, tcx).to_json(mir),
+                    "return_ty": gen.return_ty(defid, tcx).to_json(mir),
+                    "discr_ty": gen.discr_ty(tcx).to_json(mir),
+                    // One entry per generator state, giving the types of the saved locals stored
+                    // in that state.  The first three states (unresumed, returned, and panicked)
+                    // never have any saved locals.  Upvars are not included here, since they are
+                    // stored in every state.
+                    "variants": gen.state_tys(defid, tcx)
+                        .map(|tys| tys.collect::<Vec<_>>())
+                        .collect::<Vec<_>>().to_json(mir),
+                })
             }
-            &ty::TyKind::GeneratorWitness(_) => {
-                // TODO
-                json!({"kind": "GeneratorWitness"})
+            &ty::TyKind::GeneratorWitness(ref tys) => {
+                json!({
+                    "kind": "GeneratorWitness",
+                    "tys": tys.skip_binder().to_json(mir),
+                })
             }
-            &ty::TyKind::Opaque(_, _) => {
-                // TODO
-                json!({"kind": "Opaque"})
+            &ty::TyKind::Opaque(defid, ref substs) => {
+                // Monomorphization normally reveals opaque types, but some survive in places that
+                // don't get normalized, such as the saved locals of a generator.  If this one can
+                // be revealed, emit the concrete type instead.
+                let revealed = mir.state.tcx.normalize_erasing_regions(
+                    ty::ParamEnv::reveal_all(),
+                    *self,
+                );
+                if revealed != *self {
+                    return revealed.to_json(mir);
+                }
+                json!({
+                    "kind": "Opaque",
+                    "def_id": defid.to_json(mir),
+                    "substs": substs.to_json(mir),
+                })
             }
         };
 
-        let id = mir.tys.insert(*self, j);
+        let mut info = serde_json::Map::new();
+        info.insert("layout".to_owned(), layout_json(mir, *self));
+        if let ty::TyKind::Closure(defid, substs) = self.kind {
+            info.insert("closure".to_owned(), closure_info_json(mir, defid, substs));
+        }
+        let id = mir.tys.insert(*self, j, info);
         json!(id)
     }
 }
 
+/// Describe the memory layout of `ty`.  Returns `null` if the layout can't be computed, such as
+/// for unsized or still-generic types.
+pub fn layout_json<'tcx>(
+    mir: &mut MirState<'_, 'tcx>,
+    ty: ty::Ty<'tcx>,
+) -> serde_json::Value {
+    // `layout_of` normalizes under `reveal_all`, which ICEs on projections it can't resolve, so
+    // generic types are skipped entirely rather than relying on it returning an error.
+    if ty.needs_subst() {
+        return serde_json::Value::Null;
+    }
+    match mir.state.tcx.layout_of(ty::ParamEnv::reveal_all().and(ty)) {
+        Ok(layout) => layout.details.to_json(mir),
+        Err(_) => serde_json::Value::Null,
+    }
+}
+
+impl ToJson<'_> for layout::LayoutDetails {
+    fn to_json(&self, mir: &mut MirState) -> serde_json::Value {
+        json!({
+            "size": self.size.bytes(),
+            "align": self.align.abi.bytes(),
+            "fields": self.fields.to_json(mir),
+            "variants": self.variants.to_json(mir),
+            "abi": self.abi.to_json(mir),
+        })
+    }
+}
+
+impl ToJson<'_> for layout::FieldPlacement {
+    fn to_json(&self, _mir: &mut MirState) -> serde_json::Value {
+        match *self {
+            layout::FieldPlacement::Union(count) => json!({
+                "kind": "Union",
+                "count": count,
+            }),
+            layout::FieldPlacement::Array { stride, count } => json!({
+                "kind": "Array",
+                "stride": stride.bytes(),
+                "count": count,
+            }),
+            layout::FieldPlacement::Arbitrary { ref offsets, .. } => json!({
+                "kind": "Arbitrary",
+                // Offsets are indexed by source field order.  `memory_order` lists the source
+                // field indices in order of increasing offset.
+                "offsets": offsets.iter().map(|o| o.bytes()).collect::<Vec<_>>(),
+                "memory_order": self.index_by_increasing_offset().collect::<Vec<_>>(),
+            }),
+        }
+    }
+}
+
+impl ToJson<'_> for layout::Variants {
+    fn to_json(&self, mir: &mut MirState) -> serde_json::Value {
+        match *self {
+            layout::Variants::Single { index } => json!({
+                "kind": "Single",
+                "index": index.as_u32(),
+            }),
+            layout::Variants::Multiple { ref discr, ref discr_kind, discr_index, ref variants } => {
+                json!({
+                    "kind": "Multiple",
+                    "tag": discr.to_json(mir),
+                    "tag_field": discr_index,
+                    "encoding": discr_kind.to_json(mir),
+                    "variants": variants.iter().map(|v| v.to_json(mir)).collect::<Vec<_>>(),
+                })
+            },
+        }
+    }
+}
+
+impl ToJson<'_> for layout::DiscriminantKind {
+    fn to_json(&self, _mir: &mut MirState) -> serde_json::Value {
+        match *self {
+            layout::DiscriminantKind::Tag => json!({
+                "kind": "Tag",
+            }),
+            layout::DiscriminantKind::Niche {
+                dataful_variant,
+                ref niche_variants,
+                niche_start,
+            } => json!({
+                "kind": "Niche",
+                "dataful_variant": dataful_variant.as_u32(),
+                "niche_variants": [
+                    niche_variants.start().as_u32(),
+                    niche_variants.end().as_u32(),
+                ],
+                "niche_start": niche_start.to_string(),
+            }),
+        }
+    }
+}
+
+impl ToJson<'_> for layout::Abi {
+    fn to_json(&self, mir: &mut MirState) -> serde_json::Value {
+        match *self {
+            layout::Abi::Uninhabited => json!({
+                "kind": "Uninhabited",
+            }),
+            layout::Abi::Scalar(ref s) => json!({
+                "kind": "Scalar",
+                "scalar": s.to_json(mir),
+            }),
+            layout::Abi::ScalarPair(ref a, ref b) => json!({
+                "kind": "ScalarPair",
+                "scalars": [a.to_json(mir), b.to_json(mir)],
+            }),
+            layout::Abi::Vector { ref element, count } => json!({
+                "kind": "Vector",
+                "element": element.to_json(mir),
+                "count": count,
+            }),
+            layout::Abi::Aggregate { sized } => json!({
+                "kind": "Aggregate",
+                "sized": sized,
+            }),
+        }
+    }
+}
+
+impl ToJson<'_> for layout::Scalar {
+    fn to_json(&self, _mir: &mut MirState) -> serde_json::Value {
+        let value = match self.value {
+            layout::Primitive::Int(i, signed) => json!({
+                "kind": "Int",
+                "size": i.size().bytes(),
+                "signed": signed,
+            }),
+            layout::Primitive::F32 => json!({"kind": "F32"}),
+            layout::Primitive::F64 => json!({"kind": "F64"}),
+            layout::Primitive::Pointer => json!({"kind": "Pointer"}),
+        };
+        json!({
+            "value": value,
+            // Bounds are inclusive, and wrap around when `start > end`.
+            "valid_range": [
+                self.valid_range.start().to_string(),
+                self.valid_range.end().to_string(),
+            ],
+        })
+    }
+}
+
 impl ToJson<'_> for ty::ParamTy {
     fn to_json(&self, _mir: &mut MirState) -> serde_json::Value {
         json!(self.index)
@@ -536,6 +870,15 @@ impl<'tcx> ToJson<'tcx> for ty::Predicate<'tcx> {
                     "trait_proj": ppp.skip_binder().to_json(ms)
                 })
             }
+            &ty::Predicate::RegionOutlives(ref pred) => {
+                let &ty::OutlivesPredicate(a, b) = pred.skip_binder();
+                json!({
+                    "region_outlives": {
+                        "a": a.to_json(ms),
+                        "b": b.to_json(ms),
+                    }
+                })
+            }
             _ => {
                 json!("unknown_pred")
             }
@@ -612,10 +955,17 @@ impl<'tcx> ToJson<'tcx> for ty::GenericPredicates<'tcx> {
 
 impl ToJson<'_> for ty::GenericParamDef {
     fn to_json(&self, ms: &mut MirState) -> serde_json::Value {
+        let kind = match self.kind {
+            ty::GenericParamDefKind::Lifetime => "Lifetime",
+            ty::GenericParamDefKind::Type { .. } => "Type",
+            ty::GenericParamDefKind::Const => "Const",
+        };
         json!({
             "param_def": *(self.name.as_str()),
             "def_id": self.def_id.to_json(ms),
-        }) // TODO
+            "index": self.index,
+            "kind": kind,
+        })
     }
 }
 
@@ -649,6 +999,44 @@ pub trait ToJsonAg {
     ) -> serde_json::Value;
 }
 
+impl ToJson<'_> for ty::RegionKind {
+    fn to_json(&self, mir: &mut MirState) -> serde_json::Value {
+        match *self {
+            ty::RegionKind::ReEarlyBound(ref eb) => json!({
+                "kind": "EarlyBound",
+                "index": eb.index,
+                "name": eb.name.to_json(mir),
+            }),
+            ty::RegionKind::ReLateBound(debruijn, ref br) => json!({
+                "kind": "LateBound",
+                "debruijn": debruijn.as_u32(),
+                "bound": br.to_json(mir),
+            }),
+            ty::RegionKind::ReFree(ref fr) => json!({
+                "kind": "Free",
+                "scope": fr.scope.to_json(mir),
+                "bound": fr.bound_region.to_json(mir),
+            }),
+            ty::RegionKind::ReStatic => json!({"kind": "Static"}),
+            ty::RegionKind::ReVar(vid) => json!({"kind": "Var", "vid": vid.as_u32()}),
+            ty::RegionKind::ReErased => json!({"kind": "Erased"}),
+            // The remaining kinds appear only during type inference and borrow checking.
+            _ => json!({"kind": "Other", "debug": format!("{:?}", self)}),
+        }
+    }
+}
+
+impl ToJson<'_> for ty::BoundRegion {
+    fn to_json(&self, mir: &mut MirState) -> serde_json::Value {
+        match *self {
+            ty::BoundRegion::BrAnon(idx) => json!({"kind": "Anon", "index": idx}),
+            ty::BoundRegion::BrNamed(_, name) =>
+                json!({"kind": "Named", "name": name.to_json(mir)}),
+            ty::BoundRegion::BrEnv => json!({"kind": "Env"}),
+        }
+    }
+}
+
 impl<'tcx> ToJson<'tcx> for ty::subst::GenericArg<'tcx> {
     fn to_json(&self, mir: &mut MirState<'_, 'tcx>) -> serde_json::Value {
         match self.unpack() {
@@ -657,6 +1045,11 @@ impl<'tcx> ToJson<'tcx> for ty::subst::GenericArg<'tcx> {
             // TyLifetime and TyConst variants to handle non-type entries.  We emit something that
             // looks vaguely like an interned type's ID here, and handle it specially in MIR.JSON.
             ty::subst::GenericArgKind::Lifetime(_) => json!("nonty::Lifetime"),
+            // Instance IDs already distinguish different const arguments, so the value is only
+            // rendered when requested.
+            ty::subst::GenericArgKind::Const(c) if mir.state.opts.const_substs => {
+                c.eval(mir.state.tcx, ty::ParamEnv::reveal_all()).to_json(mir)
+            },
             ty::subst::GenericArgKind::Const(_) => json!("nonty::Const"),
         }
     }
@@ -702,56 +1095,68 @@ fn read_static_memory<'tcx>(
     start: usize,
     end: usize,
 ) -> &'tcx [u8] {
-    assert!(alloc.relocations().len() == 0);
+    assert!(!has_relocations(alloc, start, end));
     alloc.inspect_with_undef_and_ptr_outside_interpreter(start .. end)
 }
 
-fn render_constant_scalar<'tcx>(
+fn has_relocations(alloc: &mir::interpret::Allocation, start: usize, end: usize) -> bool {
+    let range = Size::from_bytes(start as u64) .. Size::from_bytes(end as u64);
+    alloc.relocations().range(range).len() != 0
+}
+
+type RenderConstCx<'mir, 'tcx> = interpret::InterpCx<'mir, 'tcx, RenderConstMachine>;
+
+fn render_const_cx<'mir, 'tcx>(tcx: TyCtxt<'tcx>) -> RenderConstCx<'mir, 'tcx> {
+    interpret::InterpCx::new(
+        tcx.at(DUMMY_SP),
+        ty::ParamEnv::reveal_all(),
+        RenderConstMachine,
+        (),
+    )
+}
+
+/// Get the `AllocId` for the constant allocation `alloc`, creating one on first use.  Each
+/// allocation gets exactly one ID per session, so constants that share memory are rendered as
+/// pointers into the same `allocs` entry.
+fn const_alloc_id<'tcx>(
+    mir: &mut MirState<'_, 'tcx>,
+    alloc: &'tcx interpret::Allocation,
+) -> interpret::AllocId {
+    let tcx = mir.state.tcx;
+    *mir.alloc_ids.entry(alloc as *const _)
+        .or_insert_with(|| tcx.alloc_map.lock().create_memory_alloc(alloc))
+}
+
+/// Convert an evaluated constant into an `OpTy` that can be inspected with `icx`.  Returns `None`
+/// for `ConstValue::Slice`, which has no in-memory representation of the fat pointer itself;
+/// callers should use `render_pointee` on the slice data instead.
+fn const_value_to_op<'tcx>(
     mir: &mut MirState<'_, 'tcx>,
+    val: interpret::ConstValue<'tcx>,
     ty: ty::Ty<'tcx>,
-    s: interpret::Scalar,
-) -> Option<serde_json::Value> {
-    match s {
-        interpret::Scalar::Raw { size, data } => {
-            render_constant(mir, ty, Some(s), Some((size, data)), None)
+) -> Option<interpret::OpTy<'tcx>> {
+    let tcx = mir.state.tcx;
+    let layout = tcx.layout_of(ty::ParamEnv::reveal_all().and(ty)).ok()?;
+    match val {
+        interpret::ConstValue::Scalar(s) => {
+            Some(interpret::ImmTy::from_scalar(s, layout).into())
         },
-        interpret::Scalar::Ptr(ptr) => {
-            match mir.state.tcx.alloc_map.lock().get(ptr.alloc_id) {
-                Some(ga) => match ga {
-                    interpret::GlobalAlloc::Static(def_id) => Some(json!({
-                        "kind": "static_ref",
-                        "def_id": def_id.to_json(mir),
-                    })),
-                    interpret::GlobalAlloc::Memory(alloc) => {
-                        let start = ptr.offset.bytes() as usize;
-                        render_constant(
-                            mir,
-                            ty,
-                            Some(s),
-                            None,
-                            Some((alloc, start, start)),
-                        )
-                    },
-                    _ => None,
-                },
-                None => None,
-            }
+        interpret::ConstValue::ByRef { alloc, offset } => {
+            let id = const_alloc_id(mir, alloc);
+            let ptr = interpret::Pointer::new(id, offset);
+            Some(interpret::MPlaceTy::from_aligned_ptr(ptr, layout).into())
         },
+        interpret::ConstValue::Slice { .. } => None,
     }
 }
 
-// TODO: migrate all constant rendering to use InterpCx + OpTy.  This should reduce special cases
-// and let us handle both in-memory and immediate constants.
-fn render_constant<'tcx>(
-    mir: &mut MirState<'_, 'tcx>,
+fn render_scalar_bits<'tcx>(
     ty: ty::Ty<'tcx>,
-    scalar: Option<interpret::Scalar>,
-    scalar_bits: Option<(u8, u128)>,
-    slice: Option<(&'tcx mir::interpret::Allocation, usize, usize)>,
+    size: u8,
+    bits: u128,
 ) -> Option<serde_json::Value> {
     Some(match ty.kind {
         ty::TyKind::Int(_) => {
-            let (size, bits) = scalar_bits.expect("int const had non-scalar value?");
             let mut val = bits as i128;
             if bits & (1 << (size * 8 - 1)) != 0 && size < 128 / 8 {
                 // Sign-extend to 128 bits
@@ -770,7 +1175,6 @@ fn render_constant<'tcx>(
         ty::TyKind::Bool |
         ty::TyKind::Char |
         ty::TyKind::Uint(_) => {
-            let (size, bits) = scalar_bits.expect("uint const had non-scalar value?");
             json!({
                 "kind": match ty.kind {
                     ty::TyKind::Bool => "bool",
@@ -784,7 +1188,6 @@ fn render_constant<'tcx>(
             })
         },
         ty::TyKind::Float(ast::FloatTy::F32) => {
-            let (size, bits) = scalar_bits.expect("f32 const had non-scalar value?");
             let val = f32::from_bits(bits as u32);
             json!({
                 "kind": "float",
@@ -793,7 +1196,6 @@ fn render_constant<'tcx>(
             })
         },
         ty::TyKind::Float(ast::FloatTy::F64) => {
-            let (size, bits) = scalar_bits.expect("f64 const had non-scalar value?");
             let val = f64::from_bits(bits as u64);
             json!({
                 "kind": "float",
@@ -801,44 +1203,101 @@ fn render_constant<'tcx>(
                 "val": val.to_string(),
             })
         },
+        _ => return None,
+    })
+}
 
-        // &str - for string literals
-        ty::TyKind::Ref(_, &ty::TyS {
-            kind: ty::TyKind::Str,
-            ..
-        }, hir::Mutability::Not) => {
-            let (alloc, start, end) = slice.expect("string const had non-slice value");
-            let mem = read_static_memory(alloc, start, end);
-            json!({
-                "kind": "str",
-                "val": mem,
-            })
+/// Render each of the first `count` fields of `op`.  Returns `None` if any field can't be
+/// rendered.
+fn render_fields<'mir, 'tcx>(
+    mir: &mut MirState<'_, 'tcx>,
+    icx: &RenderConstCx<'mir, 'tcx>,
+    op: interpret::OpTy<'tcx>,
+    count: usize,
+) -> Option<serde_json::Value> {
+    let mut vals = Vec::with_capacity(count);
+    for i in 0 .. count {
+        let field = icx.operand_field(op, i as u64).ok()?;
+        vals.push(render_op(mir, icx, field)?);
+    }
+    Some(vals.into())
+}
+
+/// Render the value of `op`, recursively walking through fields, array elements, and pointees.
+fn render_op<'mir, 'tcx>(
+    mir: &mut MirState<'_, 'tcx>,
+    icx: &RenderConstCx<'mir, 'tcx>,
+    op: interpret::OpTy<'tcx>,
+) -> Option<serde_json::Value> {
+    let ty = op.layout.ty;
+    Some(match ty.kind {
+        ty::TyKind::Int(_) |
+        ty::TyKind::Uint(_) |
+        ty::TyKind::Float(_) |
+        ty::TyKind::Bool |
+        ty::TyKind::Char => {
+            match icx.read_scalar(op).ok()?.not_undef().ok()? {
+                interpret::Scalar::Raw { size, data } => render_scalar_bits(ty, size, data)?,
+                interpret::Scalar::Ptr(_) => return None,
+            }
         },
 
-        // &[u8; _] - for bytestring literals
-        ty::TyKind::Ref(_, &ty::TyS {
-            kind: ty::TyKind::Array(&ty::TyS {
-                kind: ty::TyKind::Uint(ast::UintTy::U8),
-                ..
-            }, len_const),
-            ..
-        }, hir::Mutability::Not) => {
-            let len = eval_array_len(mir.state.tcx, len_const);
-            let (alloc, start, _) = slice.expect("string const had non-slice value");
-            let end = start + len;
-            let mem = read_static_memory(alloc, start, end);
-            json!({
-                "kind": "bstr",
-                "val": mem,
-            })
+        ty::TyKind::Ref(_, pointee, _) |
+        ty::TyKind::RawPtr(ty::TypeAndMut { ty: pointee, .. }) => {
+            let imm = icx.read_immediate(op).ok()?;
+            let (ptr, len) = match *imm {
+                interpret::Immediate::Scalar(s) => (s.not_undef().ok()?, None),
+                interpret::Immediate::ScalarPair(a, b) => (
+                    a.not_undef().ok()?,
+                    // For trait objects, the metadata is a vtable pointer, not a length.
+                    b.not_undef().ok().and_then(|b| b.to_machine_usize(icx).ok()),
+                ),
+            };
+            match ptr {
+                interpret::Scalar::Raw { data, .. } => {
+                    let tcx = mir.state.tcx;
+                    let layout = match ty.kind {
+                        ty::TyKind::Ref(..) =>
+                            tcx.layout_of(ty::ParamEnv::reveal_all().and(pointee)).ok(),
+                        // Raw pointers may hold any address, even when the pointee is a ZST.
+                        _ => None,
+                    };
+                    match layout {
+                        // References to ZSTs don't need to point into any allocation.
+                        Some(layout) if layout.is_zst() => json!({
+                            "kind": "ref",
+                            "pointee": render_op(
+                                mir,
+                                icx,
+                                interpret::MPlaceTy::dangling(layout, icx).into(),
+                            )?,
+                        }),
+                        _ => json!({
+                            "kind": "raw_ptr",
+                            "val": data.to_string(),
+                        }),
+                    }
+                },
+                interpret::Scalar::Ptr(ptr) => render_pointee(mir, icx, pointee, ptr, len)?,
+            }
         },
 
-        ty::TyKind::RawPtr(_) => {
-            let (_size, bits) = scalar_bits.expect("raw_ptr const had non-scalar value?");
-            json!({
-                "kind": "raw_ptr",
-                "val": bits.to_string(),
-            })
+        ty::TyKind::FnPtr(_) => {
+            let ptr = match icx.read_scalar(op).ok()?.not_undef().ok()? {
+                interpret::Scalar::Ptr(ptr) => ptr,
+                interpret::Scalar::Raw { .. } => return None,
+            };
+            let ga = mir.state.tcx.alloc_map.lock().get(ptr.alloc_id);
+            match ga? {
+                interpret::GlobalAlloc::Function(inst) => {
+                    mir.used.instances.insert(inst);
+                    json!({
+                        "kind": "fn_ptr",
+                        "def_id": inst_id_str(mir.state.tcx, inst),
+                    })
+                },
+                _ => return None,
+            }
         },
 
         ty::TyKind::FnDef(defid, ref substs) => {
@@ -849,53 +1308,59 @@ fn render_constant<'tcx>(
         },
 
         ty::TyKind::Adt(adt_def, _substs) if adt_def.is_struct() => {
-            let tyl = mir.state.tcx.layout_of(ty::ParamEnv::reveal_all().and(ty))
-                .unwrap_or_else(|e| panic!("failed to get layout of {:?}: {}", ty, e));
-            let scalar = scalar.expect("adt had non-scalar value (NYI)");
             let variant = adt_def.non_enum_variant();
             json!({
                 "kind": "struct",
-                "fields": render_constant_variant(
-                    mir,
-                    variant,
-                    interpret::ImmTy::from_scalar(scalar, tyl).into(),
-                ),
+                "fields": render_fields(mir, icx, op, variant.fields.len())?,
             })
         },
 
         ty::TyKind::Adt(adt_def, _substs) if adt_def.is_enum() => {
-            let tyl = mir.state.tcx.layout_of(ty::ParamEnv::reveal_all().and(ty))
-                .unwrap_or_else(|e| panic!("failed to get layout of {:?}: {}", ty, e));
-            let scalar = scalar.expect("adt had non-scalar value (NYI)");
-
-            let icx = interpret::InterpCx::new(
-                mir.state.tcx.at(DUMMY_SP),
-                ty::ParamEnv::reveal_all(),
-                RenderConstMachine,
-                (),
-            );
-            let op_ty = interpret::ImmTy::from_scalar(scalar, tyl).into();
-            let (_discr, variant_idx) = icx.read_discriminant(op_ty).unwrap_or_else(|e| {
-                panic!("failed to access discriminant of {:?}: {}", op_ty.layout.ty, e)
-            });
-
+            let (_discr, variant_idx) = icx.read_discriminant(op).ok()?;
             let variant = &adt_def.variants[variant_idx];
+            let op = icx.operand_downcast(op, variant_idx).ok()?;
             json!({
                 "kind": "enum",
                 "variant": variant_idx.as_u32(),
-                "fields": render_constant_variant(
-                    mir,
-                    variant,
-                    icx.operand_downcast(op_ty, variant_idx).unwrap_or_else(|e| {
-                        panic!("failed to downcast {:?} to variant {:?}: {}",
-                            op_ty.layout.ty, variant_idx, e)
-                    }),
-                ),
+                "fields": render_fields(mir, icx, op, variant.fields.len())?,
+            })
+        },
+
+        ty::TyKind::Adt(adt_def, _substs) if adt_def.is_union() => {
+            // Unions have no active field we could use to interpret the value, so we render the
+            // raw bytes instead.  This is only possible for unions that live in memory.
+            let mut j = json!({
+                "kind": "union",
+            });
+            if let Some(bytes) = read_op_bytes(icx, op) {
+                j["val"] = bytes.into();
+            }
+            j
+        },
+
+        ty::TyKind::Tuple(tys) => {
+            json!({
+                "kind": "tuple",
+                "elements": render_fields(mir, icx, op, tys.len())?,
+            })
+        },
+
+        ty::TyKind::Closure(..) => {
+            json!({
+                "kind": "closure",
+                "upvars": render_fields(mir, icx, op, op.layout.fields.count())?,
+            })
+        },
+
+        ty::TyKind::Array(..) => {
+            json!({
+                "kind": "array",
+                "elements": render_fields(mir, icx, op, op.layout.fields.count())?,
             })
         },
 
         _ => {
-            if let Some((0, _)) = scalar_bits {
+            if op.layout.is_zst() {
                 json!({
                     "kind": "zst",
                 })
@@ -906,45 +1371,106 @@ fn render_constant<'tcx>(
     })
 }
 
-fn render_constant_variant<'tcx>(
+/// Read the raw bytes of a memory-backed `op`.  Returns `None` for immediates, and for values
+/// containing pointers or undefined bytes.
+fn read_op_bytes<'mir, 'tcx>(
+    icx: &RenderConstCx<'mir, 'tcx>,
+    op: interpret::OpTy<'tcx>,
+) -> Option<Vec<u8>> {
+    let mpl = op.try_as_mplace(icx).ok()?;
+    let bytes = icx.memory.read_bytes(mpl.ptr, op.layout.size).ok()?;
+    Some(bytes.to_owned())
+}
+
+/// Render a pointer to `pointee`.  `len` gives the length metadata for pointers to slices and
+/// `str`.  Pointers into memory allocations also record the allocation and offset, so pointer
+/// identity between constants is preserved.
+fn render_pointee<'mir, 'tcx>(
     mir: &mut MirState<'_, 'tcx>,
-    variant: &'tcx ty::VariantDef,
-    op_ty: interpret::OpTy<'tcx>,
+    icx: &RenderConstCx<'mir, 'tcx>,
+    pointee: ty::Ty<'tcx>,
+    ptr: interpret::Pointer,
+    len: Option<u64>,
 ) -> Option<serde_json::Value> {
-    let icx = interpret::InterpCx::new(
-        mir.state.tcx.at(DUMMY_SP),
-        ty::ParamEnv::reveal_all(),
-        RenderConstMachine,
-        (),
-    );
-    let mut vals = Vec::with_capacity(variant.fields.len());
-    for i in 0 .. variant.fields.len() {
-        let field = icx.operand_field(op_ty, i as u64).unwrap_or_else(|e| {
-            panic!("failed to access field {} of {:?}: {}", i, op_ty.layout.ty, e)
-        });
+    let tcx = mir.state.tcx;
+    let ga = tcx.alloc_map.lock().get(ptr.alloc_id);
+    let alloc = match ga? {
+        interpret::GlobalAlloc::Static(def_id) => return Some(json!({
+            "kind": "static_ref",
+            "def_id": def_id.to_json(mir),
+            "offset": ptr.offset.bytes(),
+        })),
+        interpret::GlobalAlloc::Memory(alloc) => alloc,
+        interpret::GlobalAlloc::Function(_) => return None,
+    };
+    let start = ptr.offset.bytes() as usize;
 
-        // `try_as_mplace` always "succeeds" (returns an `MPlace`, dangling) for ZSTs.  We want it
-        // to be an immediate instead.
-        if field.layout.is_zst() {
-            vals.push(render_constant_scalar(mir, field.layout.ty, interpret::Scalar::zst())?);
-            continue;
-        }
+    let mut j = match pointee.kind {
+        // &str - for string literals
+        ty::TyKind::Str => {
+            let end = start + len? as usize;
+            json!({
+                "kind": "str",
+                "val": read_static_memory(alloc, start, end),
+            })
+        },
 
-        let val = match field.try_as_mplace(&icx) {
-            Ok(_mpl) => None,
-            Err(imm) => match *imm {
-                interpret::Immediate::Scalar(maybe_scalar) => match maybe_scalar {
-                    interpret::ScalarMaybeUndef::Scalar(s) => {
-                        render_constant_scalar(mir, field.layout.ty, s)
-                    },
-                    _ => None,
+        // &[u8; _] - for bytestring literals
+        ty::TyKind::Array(&ty::TyS {
+            kind: ty::TyKind::Uint(ast::UintTy::U8),
+            ..
+        }, len_const) if !has_relocations(
+            alloc, start, start + eval_array_len(tcx, len_const),
+        ) => {
+            let end = start + eval_array_len(tcx, len_const);
+            json!({
+                "kind": "bstr",
+                "val": read_static_memory(alloc, start, end),
+            })
+        },
+
+        ty::TyKind::Slice(elem_ty) => {
+            let len = len?;
+            let elem_layout = tcx.layout_of(ty::ParamEnv::reveal_all().and(elem_ty)).ok()?;
+            let mut vals = Vec::with_capacity(len as usize);
+            for i in 0 .. len {
+                let elem_ptr = interpret::Pointer::new(
+                    ptr.alloc_id,
+                    ptr.offset + elem_layout.size * i,
+                );
+                let elem = interpret::MPlaceTy::from_aligned_ptr(elem_ptr, elem_layout);
+                vals.push(render_op(mir, icx, elem.into())?);
+            }
+            json!({
+                "kind": "ref",
+                "pointee": {
+                    "kind": "slice",
+                    "elements": vals,
                 },
-                _ => None,
-            },
-        }?;
-        vals.push(val);
-    }
-    Some(vals.into())
+            })
+        },
+
+        _ => {
+            let layout = tcx.layout_of(ty::ParamEnv::reveal_all().and(pointee)).ok()?;
+            let mut j = json!({
+                "kind": "ref",
+            });
+            // Trait objects and custom DSTs have no rendered pointee.  Their contents can still be
+            // found through the `allocs` table.
+            if !layout.is_unsized() {
+                let mpl = interpret::MPlaceTy::from_aligned_ptr(ptr, layout);
+                if let Some(pointee) = render_op(mir, icx, mpl.into()) {
+                    j["pointee"] = pointee;
+                }
+            }
+            j
+        },
+    };
+
+    mir.used.allocs.insert(ptr.alloc_id);
+    j["alloc_id"] = alloc_id_str(tcx, ptr.alloc_id).into();
+    j["offset"] = ptr.offset.bytes().into();
+    Some(j)
 }
 
 struct RenderConstMachine;
@@ -1110,6 +1636,27 @@ impl<'tcx> ToJson<'tcx> for ty::Const<'tcx> {
             _ => {},
         }
 
+        // In generic bodies, constants may depend on the generic parameters, in which case they
+        // can't be evaluated.
+        match self.val {
+            ty::ConstKind::Param(p) => {
+                map.insert("param".to_owned(), json!({
+                    "index": p.index,
+                    "name": p.name.as_str().to_string(),
+                }));
+                return map.into();
+            },
+            ty::ConstKind::Unevaluated(_, substs, _) if substs.needs_subst() => {
+                return map.into();
+            },
+            // Rendering needs the layout of the constant's type, which isn't available for
+            // generic types.
+            ty::ConstKind::Value(_) if self.ty.needs_subst() => {
+                return map.into();
+            },
+            _ => {},
+        }
+
         let evaluated = match self.val {
             ty::ConstKind::Unevaluated(def_id, substs, promoted) => {
                 do_const_eval(mir.state.tcx, def_id, substs, promoted)
@@ -1118,14 +1665,19 @@ impl<'tcx> ToJson<'tcx> for ty::Const<'tcx> {
             _ => panic!("don't know how to translate ConstKind::{:?}", self.val),
         };
 
+        let tcx = mir.state.tcx;
+        let icx = render_const_cx(tcx);
         let rendered = match evaluated {
-            interpret::ConstValue::Scalar(s) => {
-                render_constant_scalar(mir, self.ty, s)
-            },
             interpret::ConstValue::Slice { data, start, end } => {
-                render_constant(mir, self.ty, None, None, Some((data, start, end)))
+                let id = const_alloc_id(mir, data);
+                let ptr = interpret::Pointer::new(id, Size::from_bytes(start as u64));
+                let pointee = self.ty.builtin_deref(true)
+                    .unwrap_or_else(|| panic!("slice constant has non-pointer type {:?}", self.ty))
+                    .ty;
+                render_pointee(mir, &icx, pointee, ptr, Some((end - start) as u64))
             },
-            _ => None,
+            val => const_value_to_op(mir, val, self.ty)
+                .and_then(|op| render_op(mir, &icx, op)),
         };
         if let Some(rendered) = rendered {
             map.insert("rendered".to_owned(), rendered);
@@ -1200,16 +1752,69 @@ impl<'tcx> ToJson<'tcx> for AdtInst<'tcx> {
         &self,
         mir: &mut MirState<'_, 'tcx>,
     ) -> serde_json::Value {
+        let tcx = mir.state.tcx;
+        let ty = tcx.mk_adt(self.adt, self.substs);
+
+        let mut variants = self.adt.variants.tojson(mir, self.substs);
+        let discr_ty = if self.adt.is_enum() {
+            // Record the evaluated value of each discriminant, so consumers don't need to run the
+            // `VariantDiscr::Explicit` initializers themselves.
+            for (idx, discr) in self.adt.discriminants(tcx) {
+                variants[idx.as_usize()]["discr_value"] = discr.to_string().into();
+            }
+            self.adt.repr.discr_type().to_ty(tcx).to_json(mir)
+        } else {
+            serde_json::Value::Null
+        };
+
         json!({
-            "name": adt_inst_id_str(mir.state.tcx, *self),
+            "name": adt_inst_id_str(tcx, *self),
             "kind": format!("{:?}", self.adt.adt_kind()),
-            "variants": self.adt.variants.tojson(mir, self.substs),
+            "variants": variants,
             "orig_def_id": self.adt.did.to_json(mir),
             "orig_substs": self.substs.to_json(mir),
+            "layout": layout_json(mir, ty),
+            "repr": self.adt.repr.to_json(mir),
+            "discr_ty": discr_ty,
+            "non_exhaustive": self.adt.is_variant_list_non_exhaustive(),
         })
     }
 }
 
+impl<'tcx> ToJson<'tcx> for ty::ReprOptions {
+    fn to_json(&self, mir: &mut MirState<'_, 'tcx>) -> serde_json::Value {
+        let int = match self.int {
+            Some(it) => it.to_ty(mir.state.tcx).to_json(mir),
+            None => serde_json::Value::Null,
+        };
+        json!({
+            "c": self.c(),
+            "transparent": self.transparent(),
+            "simd": self.simd(),
+            "packed": self.pack.map(|a| a.bytes()),
+            "align": self.align.map(|a| a.bytes()),
+            "int": int,
+        })
+    }
+}
+
+impl ToJson<'_> for ty::Visibility {
+    fn to_json(&self, mir: &mut MirState) -> serde_json::Value {
+        match *self {
+            ty::Visibility::Public => json!({
+                "kind": "Public",
+            }),
+            ty::Visibility::Restricted(scope) => json!({
+                "kind": "Restricted",
+                "scope": scope.to_json(mir),
+            }),
+            ty::Visibility::Invisible => json!({
+                "kind": "Invisible",
+            }),
+        }
+    }
+}
+
 impl ToJsonAg for ty::VariantDef {
     fn tojson<'tcx>(
         &self,
@@ -1220,7 +1825,8 @@ impl ToJsonAg for ty::VariantDef {
             "name": self.def_id.to_json(mir),
             "discr": self.discr.to_json(mir),
             "fields": self.fields.tojson(mir, substs),
-            "ctor_kind": self.ctor_kind.to_json(mir)
+            "ctor_kind": self.ctor_kind.to_json(mir),
+            "non_exhaustive": self.is_field_list_non_exhaustive(),
         })
     }
 }
@@ -1231,12 +1837,19 @@ impl ToJsonAg for ty::FieldDef {
         mir: &mut MirState<'_, 'tcx>,
         substs: ty::subst::SubstsRef<'tcx>,
     ) -> serde_json::Value {
-        let unsubst_ty = mir.state.tcx.type_of(self.did);
-        let ty = mir.state.tcx.subst_and_normalize_erasing_regions(
-            substs, ty::ParamEnv::reveal_all(), &unsubst_ty);
+        let tcx = mir.state.tcx;
+        let unsubst_ty = tcx.type_of(self.did);
+        let ty = if substs.needs_subst() {
+            // Generic ADT instances (from polymorphic mode) can't be normalized.
+            tcx.erase_regions(&unsubst_ty.subst(tcx, substs))
+        } else {
+            tcx.subst_and_normalize_erasing_regions(
+                substs, ty::ParamEnv::reveal_all(), &unsubst_ty)
+        };
         json!({
             "name": self.did.to_json(mir),
             "ty": ty.to_json(mir),
+            "vis": self.vis.to_json(mir),
         })
     }
 }
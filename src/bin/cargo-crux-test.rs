@@ -11,6 +11,21 @@
 //!  * `use_override_crates` (list of strings): Use Crux overrides for the named crates.  These
 //!    crates will be hidden from their downstream dependencies, causing fallback to the
 //!    verifier-friendly implementations shipped with Crux.
+//!
+//!  * `roots` (list of strings): Paths of additional functions to verify, such as
+//!    `some_dep::module::function`.  These are treated like `#[crux_test]` functions, but can be
+//!    located in any crate of the build, including dependencies.  Roots can also be given in the
+//!    comma-separated `CRUX_ROOTS` environment variable.
+//!
+//!  * `include_tests` (boolean): Also verify the standard `#[test]` functions of the package,
+//!    except those marked `#[ignore]`.  This requires a `libtest` built for Crux.  It can also be
+//!    enabled by setting `CRUX_INCLUDE_TESTS=1`.
+//!
+//!  * `cfg_test` (boolean, default `true`): Build the package under test with `--cfg test`, as
+//!    `cargo test` does, so `#[crux_test]` functions inside `#[cfg(test)]` modules are found.
+//!    This also compiles all other `#[cfg(test)]` code, which may use features (such as
+//!    `extern crate test` or `#[bench]`) that the Crux toolchain doesn't support.  Set this to
+//!    `false`, or set `CRUX_CFG_TEST=0`, to build without it.
 
 #![feature(rustc_private)]
 extern crate cargo;
@@ -23,6 +38,7 @@ use std::env;
 use std::path::PathBuf;
 use std::process::{self, Command};
 use rustc_session::config::host_triple;
+use cargo::core::Package;
 use cargo::util::command_prelude::*;
 
 fn cli() -> App {
@@ -78,8 +94,8 @@ fn cli() -> App {
         .after_help("See `cargo test --help` for more information on these options.")
 }
 
-/// Get the list of crates for which mir-verifier overrides should be used.
-fn get_override_crates() -> String {
+/// Run `f` on the list of packages that `cargo test` would test, given our command-line arguments.
+fn with_test_packages<R>(f: impl FnOnce(&[&Package]) -> R) -> R {
     // `cargo metadata` output includes the settings for each crate in the build, but gives us no
     // way to determine which crates we're actually building, as that's controlled by various
     // options that are specific to `cargo test`.  So instead we use the `cargo` library, including
@@ -102,7 +118,11 @@ fn get_override_crates() -> String {
     ).unwrap_or_else(|e| panic!("error reading compile options: {}", e));
     let pkgs = opts.spec.get_packages(&ws)
         .unwrap_or_else(|e| panic!("error listing packages: {}", e));
+    f(&pkgs)
+}
 
+/// Get the list of crates for which mir-verifier overrides should be used.
+fn get_override_crates(pkgs: &[&Package]) -> String {
     if pkgs.len() == 0 {
         return "".to_owned();
     }
@@ -149,6 +169,64 @@ fn get_package_override_crates_opt(v: Option<&toml::Value>) -> Option<String> {
     Some(overrides.join(" "))
 }
 
+/// Check whether any of the packages being tested sets `include_tests`.
+fn get_include_tests(pkgs: &[&Package]) -> bool {
+    pkgs.iter().any(|pkg| {
+        let v = pkg.manifest().custom_metadata().and_then(|v| v.get("crux"));
+        match v.and_then(|crux| crux.get("include_tests")) {
+            Some(x) => {
+                assert!(x.is_bool(), "expected `crux.include_tests` to be a boolean");
+                x.as_bool().unwrap()
+            },
+            None => false,
+        }
+    })
+}
+
+/// Check whether any of the packages being tested disables `cfg_test`.
+fn get_cfg_test(pkgs: &[&Package]) -> bool {
+    pkgs.iter().all(|pkg| {
+        let v = pkg.manifest().custom_metadata().and_then(|v| v.get("crux"));
+        match v.and_then(|crux| crux.get("cfg_test")) {
+            Some(x) => {
+                assert!(x.is_bool(), "expected `crux.cfg_test` to be a boolean");
+                x.as_bool().unwrap()
+            },
+            None => true,
+        }
+    })
+}
+
+/// Get the comma-separated list of extra roots requested by the packages being tested, combined
+/// with any given in `CRUX_ROOTS`.
+fn get_roots(pkgs: &[&Package]) -> String {
+    let mut roots = Vec::new();
+    if let Ok(s) = env::var("CRUX_ROOTS") {
+        roots.extend(s.split(',').filter(|x| x.len() > 0).map(|x| x.to_owned()));
+    }
+    for pkg in pkgs {
+        roots.extend(get_package_roots(pkg.manifest().custom_metadata()));
+    }
+    roots.join(",")
+}
+
+fn get_package_roots(v: Option<&toml::Value>) -> Vec<String> {
+    let crux = match v.and_then(|v| v.get("crux")) {
+        Some(x) => x,
+        None => return Vec::new(),
+    };
+    assert!(crux.is_table(), "expected `package.metadata.crux` to be an object");
+    let roots = match crux.get("roots") {
+        Some(x) => x,
+        None => return Vec::new(),
+    };
+    assert!(roots.is_array(), "expected `crux.roots` to be an array");
+    roots.as_array().unwrap().iter().map(|x| {
+        assert!(x.is_str(), "expected `crux.roots` items to be strings");
+        x.as_str().unwrap().to_owned()
+    }).collect()
+}
+
 fn main() {
     let cargo = env::var("CARGO").unwrap();
 
@@ -171,12 +249,22 @@ fn main() {
         PathBuf::from("mir-json-rustc-wrapper")
     };
 
-    let override_crates = get_override_crates();
+    let (override_crates, roots, include_tests, cfg_test) = with_test_packages(|pkgs| {
+        (get_override_crates(pkgs), get_roots(pkgs), get_include_tests(pkgs), get_cfg_test(pkgs))
+    });
 
-    let status = Command::new(&cargo)
+    let mut cmd = Command::new(&cargo);
+    if include_tests {
+        cmd.env("CRUX_INCLUDE_TESTS", "1");
+    }
+    if !cfg_test {
+        cmd.env("CRUX_CFG_TEST", "0");
+    }
+    let status = cmd
         .args(&args)
         .env("RUSTC_WRAPPER", wrapper_path)
         .env("CRUX_USE_OVERRIDE_CRATES", override_crates)
+        .env("CRUX_ROOTS", roots)
         .status().unwrap();
     // `code` can return `None` if the process was terminated by a signal.  We return nonzero
     // ourselves in that case.
@@ -18,6 +18,7 @@ extern crate mir_json;
 
 use mir_json::analyz;
 use mir_json::link;
+use mir_json::lib_util;
 use rustc_session::config::Externs;
 use rustc_driver::Compilation;
 use rustc_interface::interface::{Compiler, Config};
@@ -107,6 +108,8 @@ fn scrub_externs(externs: &mut Externs, use_override_crates: &HashSet<String>) {
 struct MirJsonCallbacks {
     analysis_data: Option<analyz::AnalysisData<()>>,
     use_override_crates: HashSet<String>,
+    /// Stop once the MIR has been exported, without generating any code.
+    stop_after_analysis: bool,
 }
 
 impl rustc_driver::Callbacks for MirJsonCallbacks {
@@ -138,7 +141,11 @@ impl rustc_driver::Callbacks for MirJsonCallbacks {
         queries: &'tcx Queries<'tcx>,
     ) -> Compilation {
         self.analysis_data = analyz::analyze(compiler.session(), queries).unwrap();
-        Compilation::Continue
+        if self.stop_after_analysis {
+            Compilation::Stop
+        } else {
+            Compilation::Continue
+        }
     }
 }
 
@@ -150,6 +157,43 @@ fn link_mirs(main_path: PathBuf, extern_paths: &[PathBuf], out_path: &Path) {
     link::link_crates(&mut inputs, output).unwrap();
 }
 
+/// Report each name in `CRUX_ROOTS` that doesn't match any root of the linked output.  Each crate
+/// only adds the roots it defines, so this can only be checked once all crates are linked.
+/// Otherwise, a typo would silently leave nothing to verify.
+fn check_root_names(json_path: &Path) {
+    let names = match env::var("CRUX_ROOTS") {
+        Ok(s) => s.split(',')
+            .map(|x| x.trim())
+            .filter(|x| x.len() > 0)
+            .map(|x| x.to_owned())
+            .collect::<HashSet<_>>(),
+        Err(_) => return,
+    };
+    if names.len() == 0 {
+        return;
+    }
+
+    let input = io::BufReader::new(File::open(json_path).unwrap());
+    let root_meta = link::read_root_meta(input).unwrap();
+    // Each `path` is qualified with its crate name, which may be omitted in `CRUX_ROOTS`.
+    let paths = root_meta.iter()
+        .filter_map(|meta| meta["path"].as_str())
+        .collect::<Vec<_>>();
+    let mut missing = names.iter()
+        .filter(|name| !paths.iter().any(|&path| {
+            path == name.as_str() || path.splitn(2, "::").nth(1) == Some(name.as_str())
+        }))
+        .collect::<Vec<_>>();
+    if missing.len() == 0 {
+        return;
+    }
+    missing.sort();
+    for name in missing {
+        eprintln!("error: CRUX_ROOTS: no function named `{}` in this build", name);
+    }
+    std::process::exit(1);
+}
+
 fn write_test_script(script_path: &Path, json_path: &Path) -> io::Result<()> {
     let json_name = json_path.file_name().unwrap().to_str().unwrap();
     let mut f = OpenOptions::new().write(true).create(true).truncate(true)
@@ -207,6 +251,7 @@ fn go() {
                 &mut MirJsonCallbacks {
                     analysis_data: None,
                     use_override_crates: use_override_crates.clone(),
+                    stop_after_analysis: false,
                 },
                 None,
                 None,
@@ -225,14 +270,32 @@ fn go() {
     eprintln!("test build - extract output path - {:?}", args);
     let test_path = get_output_path(&args, &use_override_crates);
 
-    args.remove(test_idx);
+    // With `CRUX_INCLUDE_TESTS`, standard `#[test]` functions are also used as roots.  These are
+    // only kept by rustc in `--test` mode, which also requires `libtest` to be available.  Since
+    // the test binary itself is replaced by our script, we stop once the MIR is exported.
+    let include_tests = lib_util::env_flag("CRUX_INCLUDE_TESTS");
+
+    // `--test` implies `--cfg test`.  Unless disabled with `CRUX_CFG_TEST=0`, we keep it after
+    // removing `--test`, so `#[crux_test]` functions inside `#[cfg(test)]` modules are still
+    // compiled.
+    let cfg_test = env::var_os("CRUX_CFG_TEST").is_none() || lib_util::env_flag("CRUX_CFG_TEST");
+
+    if !include_tests {
+        args.remove(test_idx);
+        if cfg_test {
+            args.push("--cfg".into());
+            args.push("test".into());
+        }
+    }
 
     args.push("--cfg".into());
     args.push("crux_top_level".into());
 
-    // Cargo doesn't pass a crate type for `--test` builds.  We fill in a reasonable default.
-    args.push("--crate-type".into());
-    args.push("rlib".into());
+    if !include_tests {
+        // Cargo doesn't pass a crate type for `--test` builds.  We fill in a reasonable default.
+        args.push("--crate-type".into());
+        args.push("rlib".into());
+    }
 
     eprintln!("test build - {:?}", args);
 
@@ -242,6 +305,7 @@ fn go() {
     let mut callbacks = MirJsonCallbacks {
         analysis_data: None,
         use_override_crates: use_override_crates.clone(),
+        stop_after_analysis: include_tests,
     };
     rustc_driver::run_compiler(
         &args,
@@ -260,6 +324,7 @@ fn go() {
         data.extern_mir_paths.iter().map(|x| format!(" {}", x.display())).collect::<String>(),
     );
     link_mirs(data.mir_path, &data.extern_mir_paths, &json_path);
+    check_root_names(&json_path);
 
     write_test_script(&test_path, &json_path).unwrap();
     eprintln!("generated test script {}", test_path.display());
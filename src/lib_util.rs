@@ -19,6 +19,7 @@
 
 use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
+use std::env;
 use std::fs::File;
 use std::io::{self, Read, Write, Seek, SeekFrom, Cursor, BufWriter};
 use std::path::Path;
@@ -30,6 +31,15 @@ use tar;
 
 use crate::tar_stream::{TarStream, TarEntryStream};
 
+/// Check whether the boolean environment variable `name` is set.  Any value other than the empty
+/// string or `0` counts as set.
+pub fn env_flag(name: &str) -> bool {
+    match env::var(name) {
+        Ok(s) => s != "" && s != "0",
+        Err(_) => false,
+    }
+}
+
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub struct CrateIndex {
     /// Name table.  Contains every string in the crate that looks like it might be an item name.
@@ -66,6 +76,12 @@ pub enum EntryKind {
     Trait,
     Intrinsic,
     Ty,
+    Alloc,
+    Source,
+    RootMeta,
+    TraitDef,
+    Impl,
+    GenericFn,
 }
 
 impl EntryKind {
@@ -79,6 +95,12 @@ impl EntryKind {
             Trait => "trait",
             Intrinsic => "intrinsic",
             Ty => "ty",
+            Alloc => "alloc",
+            Source => "source",
+            RootMeta => "root_meta",
+            TraitDef => "trait_def",
+            Impl => "impl",
+            GenericFn => "generic_fn",
         }
     }
 
@@ -92,6 +114,12 @@ impl EntryKind {
             Trait => "traits",
             Intrinsic => "intrinsics",
             Ty => "tys",
+            Alloc => "allocs",
+            Source => "sources",
+            RootMeta => "root_meta",
+            TraitDef => "trait_defs",
+            Impl => "impls",
+            GenericFn => "generic_fns",
         }
     }
 
@@ -99,12 +127,13 @@ impl EntryKind {
         use self::EntryKind::*;
         // Type annotation ensures we have exactly `count()` entries.
         let all: &'static [EntryKind; Self::count()] =
-            &[Fn, Adt, Static, Vtable, Trait, Intrinsic, Ty];
+            &[Fn, Adt, Static, Vtable, Trait, Intrinsic, Ty, Alloc, Source, RootMeta, TraitDef,
+              Impl, GenericFn];
         all.iter().cloned()
     }
 
     pub const fn count() -> usize {
-        7
+        13
     }
 }
 
@@ -206,7 +235,17 @@ impl EmitterState {
     ) -> Result<(), E> {
         // Collect dependencies
         let name_id = self.intern.intern(j["name"].as_str().unwrap().into());
-        self.gather_deps(name_id, j);
+        match kind {
+            // The text of a source file often contains `::`, but never refers to other entries.
+            EntryKind::Source => {
+                for (k, x) in j.as_object().unwrap() {
+                    if k != "contents" {
+                        self.gather_deps(name_id, x);
+                    }
+                }
+            },
+            _ => self.gather_deps(name_id, j),
+        }
 
         // Serialize the entry, and record its position.
         let (start, end) = write_entry(kind, j)?;
@@ -308,6 +347,18 @@ impl<W: Write> Emitter<W> {
         write!(self.writer, ",")?;
         self.emit_table_from(EntryKind::Intrinsic, j)?;
         write!(self.writer, ",")?;
+        self.emit_table_from(EntryKind::Alloc, j)?;
+        write!(self.writer, ",")?;
+        self.emit_table_from(EntryKind::Source, j)?;
+        write!(self.writer, ",")?;
+        self.emit_table_from(EntryKind::RootMeta, j)?;
+        write!(self.writer, ",")?;
+        self.emit_table_from(EntryKind::TraitDef, j)?;
+        write!(self.writer, ",")?;
+        self.emit_table_from(EntryKind::Impl, j)?;
+        write!(self.writer, ",")?;
+        self.emit_table_from(EntryKind::GenericFn, j)?;
+        write!(self.writer, ",")?;
         write!(self.writer, "\"roots\":")?;
         serde_json::to_writer(&mut self.writer, &j["roots"])?;
         write!(self.writer, "}}")?;
@@ -416,6 +467,25 @@ pub struct Output {
     /// key into this table.  This encoding avoids exponential blowup when large types appear
     /// repeatedly within a crate.
     pub tys: Vec<serde_json::Value>,
+    /// Memory allocations referenced by constants and statics.  Each entry gives the raw bytes of
+    /// the allocation, along with any pointers it contains to other allocations, statics, or
+    /// functions.
+    pub allocs: Vec<serde_json::Value>,
+    /// Source files containing the spans mentioned in this crate, with a hash of each file's
+    /// contents.
+    pub sources: Vec<serde_json::Value>,
+    /// Per-test configuration for each entry in `roots`, as given by the arguments of
+    /// `#[crux_test]`.  Each entry has the same name as its root.
+    pub root_meta: Vec<serde_json::Value>,
+    /// Generic definitions of the traits declared in this crate, giving each trait's items and
+    /// the impls of it that are visible here.
+    pub trait_defs: Vec<serde_json::Value>,
+    /// Trait impls declared in this crate, mapping each trait item to the impl item that
+    /// implements it.
+    pub impls: Vec<serde_json::Value>,
+    /// Generic MIR bodies of the functions in this crate that require monomorphization.  These
+    /// use the same format as `fns`, with added `generics` and `predicates`.
+    pub generic_fns: Vec<serde_json::Value>,
     /// Entry points for this crate.
     pub roots: Vec<String>,
 }
@@ -430,6 +500,12 @@ impl JsonOutput for Output {
             EntryKind::Trait => self.traits.push(j),
             EntryKind::Intrinsic => self.intrinsics.push(j),
             EntryKind::Ty => self.tys.push(j),
+            EntryKind::Alloc => self.allocs.push(j),
+            EntryKind::Source => self.sources.push(j),
+            EntryKind::RootMeta => self.root_meta.push(j),
+            EntryKind::TraitDef => self.trait_defs.push(j),
+            EntryKind::Impl => self.impls.push(j),
+            EntryKind::GenericFn => self.generic_fns.push(j),
         }
         Ok(())
     }
@@ -73,6 +73,29 @@ where R: Read + Seek, W: Write {
 
     let mut seen_names = HashSet::new();
     let mut worklist = roots.clone();
+    // Trait definitions and impls are never referenced from function bodies, but are needed to
+    // enumerate the possible targets of generic calls.  Likewise, generic function bodies are
+    // never roots.  When they were requested, we keep all of them, along with anything they
+    // mention that was emitted.
+    let mut keep_kinds = Vec::new();
+    if lib_util::env_flag("MIR_JSON_TRAIT_DEFS") {
+        keep_kinds.push(EntryKind::TraitDef);
+        keep_kinds.push(EntryKind::Impl);
+    }
+    if lib_util::env_flag("MIR_JSON_POLYMORPHIC") {
+        keep_kinds.push(EntryKind::GenericFn);
+    }
+    if keep_kinds.len() > 0 {
+        for (&id, def_list) in &defs {
+            let keep = def_list.iter().any(|&(crate_num, local_id)| {
+                let locations = &indexes[crate_num].items[&local_id].locations;
+                keep_kinds.iter().any(|kind| locations.contains_key(kind))
+            });
+            if keep && seen_names.insert(id) {
+                worklist.push(id);
+            }
+        }
+    }
     while let Some(id) = worklist.pop() {
         // Look for deps in all crates.  It seems like different sets of entries for an item can
         // appear in different crates, though I'm not sure why.
@@ -150,6 +173,18 @@ where R: Read + Seek, W: Write {
     Ok(())
 }
 
+#[derive(Deserialize)]
+struct LinkedRootMeta {
+    root_meta: Vec<serde_json::Value>,
+}
+
+/// Read the `root_meta` table from the output of `link_crates`.  The other tables are skipped
+/// without being fully parsed.
+pub fn read_root_meta<R: Read>(input: R) -> serde_json::Result<Vec<serde_json::Value>> {
+    let linked: LinkedRootMeta = serde_json::from_reader(input)?;
+    Ok(linked.root_meta)
+}
+
 pub fn gather_calls<R: Read + Seek>(
     inputs: &mut [R],
 ) -> serde_cbor::Result<(InternTable, Vec<(StringId, StringId)>)> {